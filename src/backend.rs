@@ -0,0 +1,52 @@
+pub mod local;
+
+use crate::{
+    error::Error,
+    requests::{
+        CreateBlobResponse, CreateCommitResponse, CreateTreeResponse, FileOutputFormat,
+        GetBranchResponse, GetTreeResponse,
+    },
+    versioning::ManagedExtensions,
+};
+
+/*
+ * The operations the tool needs from the place the extension repositories and the registry are
+ * stored, modelled after the GitHub contents and Git data APIs. Files and blobs get requested
+ * from multiple threads at once.
+ */
+pub trait Backend: Sync {
+    fn get_file(
+        &self,
+        repository: &str,
+        path: &str,
+        branch: &str,
+        output_format: &FileOutputFormat,
+    ) -> Result<String, Error>;
+
+    /*
+     * Lists every entry of the tree of the given branch or commit, including the entries of all
+     * of its subtrees.
+     */
+    fn get_tree(&self, repository: &str, reference: &str) -> Result<GetTreeResponse, Error>;
+
+    fn get_branch(&self, repository: &str, branch: &str) -> Result<GetBranchResponse, Error>;
+
+    fn create_blob(&self, content: String, encoding: String) -> Result<CreateBlobResponse, Error>;
+
+    fn create_tree(
+        &self,
+        base_tree: String,
+        managed_extensions: ManagedExtensions,
+    ) -> Result<CreateTreeResponse, Error>;
+
+    fn create_commit(
+        &self,
+        message: String,
+        tree_sha: String,
+        parent_commit_sha: String,
+        author_name: String,
+        author_email: String,
+    ) -> Result<CreateCommitResponse, Error>;
+
+    fn update_reference(&self, commit_sha: String) -> Result<(), Error>;
+}
@@ -0,0 +1,337 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use reqwest::StatusCode;
+
+use super::Backend;
+use crate::{
+    error::Error,
+    requests::{
+        Commit, CommitCommit, CreateBlobResponse, CreateCommitResponse, CreateTreeResponse,
+        FileOutputFormat, GetBranchResponse, GetTreeEntryResponse, GetTreeResponse, Tree,
+    },
+    utils,
+    versioning::ManagedExtensions,
+};
+
+type TreeEntries = Vec<(String, Option<String>)>;
+
+// The SHA Git assigns to a tree without any entries
+const EMPTY_TREE_SHA: &str = "4b825dc642cb6eb9a060e54bf8d69288fbee4904";
+
+/*
+ * A backend which treats every branch of every repository as a plain directory, located at
+ * <root>/<owner>/<repository>/<branch>. Blobs, trees and commits are kept in memory until the
+ * reference gets updated, at which point the changes get written to the registry directory.
+ *
+ * Branches have no history, the SHA of their commit is the SHA of the tree of their directory.
+ * Commits handed out by get_branch can be read from as a reference, they resolve to the directory
+ * of their branch.
+ */
+pub struct Local {
+    root: PathBuf,
+    registry_repository: String,
+    registry_branch: String,
+    blobs: Mutex<HashMap<String, Vec<u8>>>,
+    trees: Mutex<HashMap<String, TreeEntries>>,
+    commits: Mutex<HashMap<String, (String, String)>>,
+    heads: Mutex<HashMap<String, String>>,
+}
+
+impl Local {
+    pub fn new(root: PathBuf) -> Result<Local, Error> {
+        if !root.is_dir() {
+            return Err(Error::Environment {
+                variable: String::from("LOCAL_PATH"),
+                message: format!("{} is not a directory", root.display()),
+            });
+        }
+
+        Ok(Local {
+            root,
+            registry_repository: utils::env::registry_repository(),
+            registry_branch: utils::env::registry_branch(),
+            blobs: Mutex::new(HashMap::new()),
+            trees: Mutex::new(HashMap::new()),
+            commits: Mutex::new(HashMap::new()),
+            heads: Mutex::new(HashMap::new()),
+        })
+    }
+
+    fn branch_directory(&self, repository: &str, reference: &str) -> PathBuf {
+        let heads = self.heads.lock().unwrap();
+        let branch = heads
+            .get(&format!("{repository}@{reference}"))
+            .map_or(reference, String::as_str);
+
+        self.root.join(repository).join(branch)
+    }
+
+    fn io_error(path: &Path, error: std::io::Error) -> Error {
+        Error::Io {
+            path: path.display().to_string(),
+            error,
+        }
+    }
+
+    /*
+     * Collects the entries of the directory and all of its subdirectories the way Git would list
+     * them, skipping empty directories.
+     */
+    fn walk(
+        directory: &Path,
+        prefix: &str,
+        tree: &mut Vec<GetTreeEntryResponse>,
+    ) -> Result<(), Error> {
+        for entry in fs::read_dir(directory).map_err(|err| Self::io_error(directory, err))? {
+            let entry = entry.map_err(|err| Self::io_error(directory, err))?;
+            let entry_path = entry.path();
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name == ".git" {
+                continue;
+            }
+
+            let path = format!("{prefix}{name}");
+            let file_type = entry
+                .file_type()
+                .map_err(|err| Self::io_error(&entry_path, err))?;
+
+            if file_type.is_dir() {
+                let Some(sha) = utils::git::tree_sha(&entry_path)
+                    .map_err(|err| Self::io_error(&entry_path, err))?
+                else {
+                    continue;
+                };
+
+                Self::walk(&entry_path, &format!("{path}/"), tree)?;
+                tree.push(GetTreeEntryResponse {
+                    path,
+                    etype: String::from("tree"),
+                    sha,
+                });
+            } else {
+                tree.push(GetTreeEntryResponse {
+                    path,
+                    etype: String::from("blob"),
+                    sha: utils::git::blob_sha(
+                        &fs::read(&entry_path).map_err(|err| Self::io_error(&entry_path, err))?,
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
+    fn remove_file(branch_directory: &Path, path: &Path) -> Result<(), Error> {
+        if let Err(err) = fs::remove_file(path)
+            && err.kind() != std::io::ErrorKind::NotFound
+        {
+            return Err(Self::io_error(path, err));
+        }
+
+        // Git does not track empty directories, so neither should the registry directory
+        let mut parent = path.parent();
+        while let Some(directory) = parent {
+            if directory == branch_directory
+                || fs::read_dir(directory).map_or(true, |mut entries| entries.next().is_some())
+            {
+                break;
+            }
+
+            fs::remove_dir(directory).map_err(|err| Self::io_error(directory, err))?;
+            parent = directory.parent();
+        }
+
+        Ok(())
+    }
+}
+
+impl Backend for Local {
+    fn get_file(
+        &self,
+        repository: &str,
+        path: &str,
+        branch: &str,
+        output_format: &FileOutputFormat,
+    ) -> Result<String, Error> {
+        let file_path = self.branch_directory(repository, branch).join(path);
+
+        let content = fs::read(&file_path).map_err(|err| Self::io_error(&file_path, err))?;
+
+        match output_format {
+            FileOutputFormat::UTF8 => String::from_utf8(content).map_err(|err| Error::Encoding {
+                path: file_path.display().to_string(),
+                message: err.to_string(),
+            }),
+            FileOutputFormat::BASE64 => Ok(BASE64_STANDARD.encode(content)),
+        }
+    }
+
+    fn get_tree(&self, repository: &str, reference: &str) -> Result<GetTreeResponse, Error> {
+        let mut tree = vec![];
+        Self::walk(&self.branch_directory(repository, reference), "", &mut tree)?;
+        tree.sort_unstable_by(|a, b| a.path.cmp(&b.path));
+
+        Ok(GetTreeResponse {
+            tree,
+            truncated: false,
+        })
+    }
+
+    fn get_branch(&self, repository: &str, branch: &str) -> Result<GetBranchResponse, Error> {
+        let branch_directory = self.branch_directory(repository, branch);
+
+        let sha = utils::git::tree_sha(&branch_directory)
+            .map_err(|err| Self::io_error(&branch_directory, err))?
+            .unwrap_or_else(|| String::from(EMPTY_TREE_SHA));
+
+        self.heads
+            .lock()
+            .unwrap()
+            .insert(format!("{repository}@{sha}"), branch.to_string());
+
+        Ok(GetBranchResponse {
+            commit: Commit {
+                sha: sha.clone(),
+                commit: CommitCommit { tree: Tree { sha } },
+            },
+        })
+    }
+
+    fn create_blob(&self, content: String, encoding: String) -> Result<CreateBlobResponse, Error> {
+        let content = utils::git::decode(&content, &encoding)?;
+        let sha = utils::git::blob_sha(&content);
+
+        self.blobs.lock().unwrap().insert(sha.clone(), content);
+
+        Ok(CreateBlobResponse { sha })
+    }
+
+    fn create_tree(
+        &self,
+        base_tree: String,
+        managed_extensions: ManagedExtensions,
+    ) -> Result<CreateTreeResponse, Error> {
+        let mut tree = managed_extensions
+            .into_iter()
+            .flat_map(|managed_extension| managed_extension.2)
+            .collect::<TreeEntries>();
+        tree.sort_unstable();
+
+        let sha = utils::git::blob_sha(
+            tree.iter()
+                .fold(base_tree, |description, (path, sha)| {
+                    description + "\n" + path + " " + sha.as_deref().unwrap_or("-")
+                })
+                .as_bytes(),
+        );
+
+        self.trees.lock().unwrap().insert(sha.clone(), tree);
+
+        Ok(CreateTreeResponse { sha })
+    }
+
+    fn create_commit(
+        &self,
+        message: String,
+        tree_sha: String,
+        parent_commit_sha: String,
+        _author_name: String,
+        _author_email: String,
+    ) -> Result<CreateCommitResponse, Error> {
+        let sha =
+            utils::git::blob_sha(format!("{tree_sha}\n{parent_commit_sha}\n{message}").as_bytes());
+
+        self.commits
+            .lock()
+            .unwrap()
+            .insert(sha.clone(), (tree_sha, parent_commit_sha));
+
+        Ok(CreateCommitResponse { sha })
+    }
+
+    fn update_reference(&self, commit_sha: String) -> Result<(), Error> {
+        let repository = &self.registry_repository;
+        let branch = &self.registry_branch;
+        let branch_directory = self.branch_directory(repository, branch);
+
+        let Some((tree_sha, parent_commit_sha)) = self.commits.lock().unwrap().remove(&commit_sha)
+        else {
+            return Err(Error::Status {
+                path: format!("{repository}@{commit_sha}"),
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+            });
+        };
+
+        // Mirror GitHub, which refuses reference updates that are not a fast forward
+        if self.get_branch(repository, branch)?.commit.sha != parent_commit_sha {
+            return Err(Error::NotFastForward {
+                path: format!("{repository}@{branch}"),
+            });
+        }
+
+        let tree = self
+            .trees
+            .lock()
+            .unwrap()
+            .remove(&tree_sha)
+            .unwrap_or_default();
+        let blobs = self.blobs.lock().unwrap();
+        let mut existing_blobs = HashMap::new();
+
+        // Like GitHub, blobs which already exist in the registry can be referred to by their SHA
+        if tree
+            .iter()
+            .any(|(_, sha)| sha.as_ref().is_some_and(|sha| !blobs.contains_key(sha)))
+        {
+            let registry_directory = self.root.join(repository).join(branch);
+            let mut registry_tree = vec![];
+            Self::walk(&registry_directory, "", &mut registry_tree)?;
+
+            for entry in registry_tree {
+                if entry.etype == "blob" && !blobs.contains_key(&entry.sha) {
+                    let file_path = registry_directory.join(&entry.path);
+                    let content =
+                        fs::read(&file_path).map_err(|err| Self::io_error(&file_path, err))?;
+                    existing_blobs.insert(entry.sha, content);
+                }
+            }
+        }
+
+        let blob = |sha: &String| blobs.get(sha).or_else(|| existing_blobs.get(sha));
+
+        // Every blob is checked up front, so a missing one can't leave the directory half updated
+        if let Some((path, Some(sha))) = tree
+            .iter()
+            .find(|(_, sha)| sha.as_ref().is_some_and(|sha| blob(sha).is_none()))
+        {
+            return Err(Error::Status {
+                path: format!("{repository}/{path}@{sha}"),
+                status: StatusCode::UNPROCESSABLE_ENTITY,
+            });
+        }
+
+        for (path, sha) in tree {
+            let file_path = branch_directory.join(&path);
+
+            if let Some(sha) = sha {
+                let content = blob(&sha).unwrap();
+
+                if let Some(directory) = file_path.parent() {
+                    fs::create_dir_all(directory).map_err(|err| Self::io_error(directory, err))?;
+                }
+                fs::write(&file_path, content).map_err(|err| Self::io_error(&file_path, err))?;
+            } else {
+                Self::remove_file(&branch_directory, &file_path)?;
+            }
+        }
+
+        Ok(())
+    }
+}
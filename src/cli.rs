@@ -0,0 +1,317 @@
+use std::env;
+
+use clap::{Args, Parser, Subcommand};
+
+/*
+ * The command line interface of the tool. Every flag mirrors an environment variable, flags which
+ * are passed get exported as their environment variable so the rest of the tool only has to read
+ * its configuration from the environment.
+ */
+#[derive(Parser)]
+#[command(
+    version,
+    about = "Compares versioning files between an extension repository and the extensions registry, and pushes updates to the registry as needed."
+)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "GITHUB_TOKEN",
+        hide_env_values = true,
+        help = "The GitHub token used for reading the repositories and updating the registry"
+    )]
+    github_token: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "REGISTRY_REPOSITORY",
+        help = "The registry repository [default: paperback-community/extensions]"
+    )]
+    registry_repository: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "REGISTRY_BRANCH",
+        help = "The branch of the registry repository [default: master]"
+    )]
+    registry_branch: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "REPOSITORY_OWNER",
+        help = "The owner the extension repositories have to belong to [default: paperback-community]"
+    )]
+    repository_owner: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "BACKEND",
+        help = "Where the repositories are stored, either \"github\" or \"local\" [default: github]"
+    )]
+    backend: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "LOCAL_PATH",
+        help = "The directory containing the repositories when using the local backend"
+    )]
+    local_path: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "COMMIT_MESSAGE",
+        help = "The message of the commit to the registry"
+    )]
+    commit_message: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "COMMIT_AUTHOR_NAME",
+        help = "The author name of the commit to the registry [default: github-actions[bot]]"
+    )]
+    commit_author_name: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "COMMIT_AUTHOR_EMAIL",
+        help = "The author email of the commit to the registry [default: github-actions[bot]@users.noreply.github.com]"
+    )]
+    commit_author_email: Option<String>,
+
+    #[arg(
+        long,
+        global = true,
+        env = "LOG_LEVEL",
+        help = "The level at which the tool logs [default: INFO]"
+    )]
+    log_level: Option<String>,
+
+    // Not read from the environment by clap, as DRY_RUN also accepts "1"
+    #[arg(
+        long,
+        global = true,
+        help = "Print the changes instead of committing them to the registry [env: DRY_RUN]"
+    )]
+    dry_run: bool,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    #[command(about = "Sync the extensions of a repository to the registry, the default command")]
+    Sync(SyncArgs),
+    #[command(about = "Print the changes a sync would commit to the registry")]
+    Diff(SyncArgs),
+    #[command(about = "Report inconsistencies between the files in the registry as JSON")]
+    Verify,
+    #[command(about = "Rebuild the metadata file of a branch from the extension repositories")]
+    Repair(RepairArgs),
+    #[command(
+        about = "Take an extension down from a branch of the registry, preventing it from getting added again"
+    )]
+    Remove(RemoveArgs),
+    #[command(
+        about = "Promote extensions from a testing branch of the registry to its stable branch"
+    )]
+    Promote(PromoteArgs),
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    #[arg(
+        long,
+        env = "REPOSITORY",
+        help = "The repository to get the extensions from, as <owner>/<repository_name>"
+    )]
+    repository: Option<String>,
+
+    #[arg(
+        long,
+        env = "BRANCH",
+        help = "The branch of the extensions, as <paperback_major_minor_semver>/<stable/testing>"
+    )]
+    branch: Option<String>,
+
+    #[arg(
+        long,
+        env = "REPOSITORY_BRANCH",
+        help = "The branch of the repository the extensions are published on [default: gh-pages]"
+    )]
+    repository_branch: Option<String>,
+
+    #[arg(
+        long,
+        env = "ROLLBACK",
+        help = "A comma separated list of extension ids which may be replaced by a lower version from the repository"
+    )]
+    rollback: Option<String>,
+
+    #[arg(
+        long,
+        env = "CHANGED_CONTENT",
+        help = "What happens to extensions whose content changed without a version bump, either \"warn\", \"fail\" or \"update\" [default: warn]"
+    )]
+    changed_content: Option<String>,
+
+    #[arg(
+        long,
+        env = "TESTING_BADGES",
+        help = "A comma separated list of badge labels which may only be used on testing branches [default: Beta, Experimental]"
+    )]
+    testing_badges: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RepairArgs {
+    #[arg(
+        long,
+        env = "BRANCH",
+        help = "The branch of which the metadata file is rebuilt, as <paperback_major_minor_semver>/<stable/testing>"
+    )]
+    branch: Option<String>,
+
+    #[arg(
+        long,
+        env = "REPOSITORIES",
+        help = "A comma separated list of the extension repositories, in addition to the ones in the metadata file"
+    )]
+    repositories: Option<String>,
+
+    #[arg(
+        long,
+        env = "REPOSITORY_BRANCH",
+        help = "The branch of the repositories the extensions are published on [default: gh-pages]"
+    )]
+    repository_branch: Option<String>,
+}
+
+#[derive(Args)]
+pub struct RemoveArgs {
+    #[arg(
+        env = "EXTENSION_ID",
+        help = "The id of the extension which is taken down"
+    )]
+    id: String,
+
+    #[arg(
+        long,
+        env = "BRANCH",
+        help = "The branch the extension is taken down from, as <paperback_major_minor_semver>/<stable/testing>"
+    )]
+    branch: Option<String>,
+
+    #[arg(
+        long,
+        env = "TAKEDOWN_REASON",
+        help = "Why the extension is taken down, recorded in the takedowns file"
+    )]
+    reason: Option<String>,
+}
+
+#[derive(Args)]
+pub struct PromoteArgs {
+    #[arg(
+        env = "EXTENSION_ID",
+        required = true,
+        value_delimiter = ',',
+        help = "The ids of the extensions which are promoted, separated by spaces or commas"
+    )]
+    ids: Vec<String>,
+
+    #[arg(
+        long,
+        env = "BRANCH",
+        help = "The stable branch the extensions are promoted to from its testing branch, as <paperback_major_minor_semver>/stable"
+    )]
+    branch: Option<String>,
+
+    #[arg(
+        long,
+        env = "TESTING_BADGES",
+        help = "A comma separated list of badge labels which may only be used on testing branches [default: Beta, Experimental]"
+    )]
+    testing_badges: Option<String>,
+}
+
+impl Cli {
+    /*
+     * Parses the arguments and exports the passed flags, running the sync when no command was
+     * given to stay compatible with the action.
+     */
+    pub fn parse_and_export() -> Command {
+        let cli = Cli::parse();
+
+        export("GITHUB_TOKEN", cli.github_token.as_deref());
+        export("REGISTRY_REPOSITORY", cli.registry_repository.as_deref());
+        export("REGISTRY_BRANCH", cli.registry_branch.as_deref());
+        export("REPOSITORY_OWNER", cli.repository_owner.as_deref());
+        export("BACKEND", cli.backend.as_deref());
+        export("LOCAL_PATH", cli.local_path.as_deref());
+        export("COMMIT_MESSAGE", cli.commit_message.as_deref());
+        export("COMMIT_AUTHOR_NAME", cli.commit_author_name.as_deref());
+        export("COMMIT_AUTHOR_EMAIL", cli.commit_author_email.as_deref());
+        export("LOG_LEVEL", cli.log_level.as_deref());
+        export("DRY_RUN", cli.dry_run.then_some("true"));
+
+        match &cli.command {
+            Some(Command::Sync(args)) => args.export(),
+            Some(Command::Diff(args)) => {
+                args.export();
+                export("DRY_RUN", Some("true"));
+            }
+            Some(Command::Repair(args)) => {
+                export("BRANCH", args.branch.as_deref());
+                export("REPOSITORIES", args.repositories.as_deref());
+                export("REPOSITORY_BRANCH", args.repository_branch.as_deref());
+            }
+            Some(Command::Remove(args)) => {
+                export("EXTENSION_ID", Some(&args.id));
+                export("BRANCH", args.branch.as_deref());
+                export("TAKEDOWN_REASON", args.reason.as_deref());
+            }
+            Some(Command::Promote(args)) => {
+                export("EXTENSION_ID", Some(&args.ids.join(",")));
+                export("BRANCH", args.branch.as_deref());
+                export("TESTING_BADGES", args.testing_badges.as_deref());
+            }
+            Some(Command::Verify) | None => (),
+        }
+
+        cli.command.unwrap_or(Command::Sync(SyncArgs {
+            repository: None,
+            branch: None,
+            repository_branch: None,
+            rollback: None,
+            changed_content: None,
+            testing_badges: None,
+        }))
+    }
+}
+
+impl SyncArgs {
+    fn export(&self) {
+        export("REPOSITORY", self.repository.as_deref());
+        export("BRANCH", self.branch.as_deref());
+        export("REPOSITORY_BRANCH", self.repository_branch.as_deref());
+        export("ROLLBACK", self.rollback.as_deref());
+        export("CHANGED_CONTENT", self.changed_content.as_deref());
+        export("TESTING_BADGES", self.testing_badges.as_deref());
+    }
+}
+
+fn export(variable: &str, value: Option<&str>) {
+    if let Some(value) = value {
+        // SAFETY: the arguments get exported before the tool spawns any other threads
+        unsafe { env::set_var(variable, value) };
+    }
+}
@@ -0,0 +1,233 @@
+use std::{fmt, io};
+
+use reqwest::{StatusCode, blocking::Response};
+
+#[derive(Debug)]
+pub enum Error {
+    Environment {
+        variable: String,
+        message: String,
+    },
+    #[cfg(feature = "dotenv")]
+    Dotenv(String),
+    Logger(String),
+    Client(String),
+    Request {
+        path: String,
+        message: String,
+    },
+    Status {
+        path: String,
+        status: StatusCode,
+    },
+    RateLimited {
+        path: String,
+        status: StatusCode,
+    },
+    NotFastForward {
+        path: String,
+    },
+    TruncatedTree {
+        path: String,
+    },
+    Io {
+        path: String,
+        error: io::Error,
+    },
+    Deserialize {
+        path: String,
+        message: String,
+    },
+    Serialize {
+        path: String,
+        message: String,
+    },
+    Encoding {
+        path: String,
+        message: String,
+    },
+    TypesVersion {
+        registry: String,
+        repository: String,
+    },
+    InvalidTypesVersion {
+        version: String,
+    },
+    Extension {
+        id: String,
+        error: Box<Error>,
+    },
+    Drift {
+        count: usize,
+    },
+    ChangedContent {
+        ids: Vec<String>,
+    },
+    Promotion {
+        id: String,
+        reason: String,
+    },
+}
+
+impl Error {
+    /*
+     * GitHub signals an exhausted (secondary) rate limit with a 403 or 429 status code together
+     * with either an empty X-RateLimit-Remaining header or a Retry-After header. Secondary rate
+     * limits are not guaranteed to come with either header, those are recognized by their message.
+     */
+    pub fn from_response(path: &str, response: Response) -> Error {
+        let status = response.status();
+        let headers = response.headers();
+
+        if (status == StatusCode::FORBIDDEN || status == StatusCode::TOO_MANY_REQUESTS)
+            && (headers
+                .get("X-RateLimit-Remaining")
+                .is_some_and(|remaining| remaining == "0")
+                || headers.contains_key("Retry-After")
+                || response
+                    .text()
+                    .is_ok_and(|body| body.to_lowercase().contains("rate limit")))
+        {
+            return Error::RateLimited {
+                path: path.to_string(),
+                status,
+            };
+        }
+
+        Error::Status {
+            path: path.to_string(),
+            status,
+        }
+    }
+
+    /*
+     * Whether sending the same request again might succeed.
+     */
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Request { .. } | Error::RateLimited { .. } => true,
+            Error::Status { status, .. } => status.is_server_error(),
+            _ => false,
+        }
+    }
+
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Status { status, .. } | Error::RateLimited { status, .. } => Some(*status),
+            Error::Extension { error, .. } => error.status(),
+            _ => None,
+        }
+    }
+
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::Io { error, .. } => error.kind() == io::ErrorKind::NotFound,
+            Error::Extension { error, .. } => error.is_not_found(),
+            _ => self.status() == Some(StatusCode::NOT_FOUND),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Environment { variable, message } => {
+                write!(
+                    f,
+                    "The {variable} environment variable is invalid: {message}"
+                )
+            }
+            #[cfg(feature = "dotenv")]
+            Error::Dotenv(message) => write!(
+                f,
+                "An error occurred wile trying to load the .env file: {message}"
+            ),
+            Error::Logger(message) => {
+                write!(
+                    f,
+                    "Something went wrong while initializing the logger: {message}"
+                )
+            }
+            Error::Client(message) => write!(
+                f,
+                "Something went wrong while creating the request client: {message}"
+            ),
+            Error::Request { path, message } => write!(
+                f,
+                "Something went wrong while making the request for {path}: {message}"
+            ),
+            Error::Status { path, status } if *status == StatusCode::NOT_FOUND => {
+                write!(f, "The requested resource {path} was not found")
+            }
+            Error::Status { path, status } if *status == StatusCode::FORBIDDEN => write!(
+                f,
+                "Access to {path} was forbidden, check the permissions of the GitHub token"
+            ),
+            Error::Status { path, status } => write!(
+                f,
+                "The response for {path} was undesired, status code: {status}"
+            ),
+            Error::RateLimited { path, status } => write!(
+                f,
+                "The GitHub API rate limit was exceeded while requesting {path}, status code: {status}"
+            ),
+            Error::Io { path, error } => {
+                write!(f, "Something went wrong while accessing {path}: {error}")
+            }
+            Error::NotFastForward { path } => write!(
+                f,
+                "The reference {path} moved while the registry was being updated"
+            ),
+            Error::TruncatedTree { path } => write!(
+                f,
+                "The tree of {path} has too many entries to be listed at once"
+            ),
+            Error::Deserialize { path, message } => write!(
+                f,
+                "Something went wrong while deserializing the JSON of {path}: {message}"
+            ),
+            Error::Serialize { path, message } => write!(
+                f,
+                "Something went wrong while serializing {path} to JSON: {message}"
+            ),
+            Error::Encoding { path, message } => write!(
+                f,
+                "Something went wrong while encoding the content of {path}: {message}"
+            ),
+            Error::TypesVersion {
+                registry,
+                repository,
+            } => write!(
+                f,
+                "The repository was build with a @paperback/types version {repository} which was too low, expected version {registry} or higher"
+            ),
+            Error::InvalidTypesVersion { version } => write!(
+                f,
+                "The repository was build with a @paperback/types version {version} which is not valid semver"
+            ),
+            Error::Extension { id, error } => write!(f, "The {id} extension failed: {error}"),
+            Error::Drift { count } => write!(
+                f,
+                "The registry is inconsistent, {count} problems were found"
+            ),
+            Error::ChangedContent { ids } => write!(
+                f,
+                "The content of extensions changed without a version bump: {}",
+                ids.join(", ")
+            ),
+            Error::Promotion { id, reason } => {
+                write!(f, "The {id} extension can't be promoted: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { error, .. } => Some(error),
+            Error::Extension { error, .. } => Some(error.as_ref()),
+            _ => None,
+        }
+    }
+}
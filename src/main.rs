@@ -1,36 +1,147 @@
-use std::{collections::HashMap, env, process::ExitCode};
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    path::PathBuf,
+    process::ExitCode,
+};
 
+mod backend;
+use backend::{Backend, local::Local};
+mod cli;
+use cli::{Cli, Command};
+mod error;
+use error::Error;
+mod promote;
+mod registry;
+mod remove;
+mod repair;
 mod requests;
 use requests::{FileOutputFormat, Requests};
+use reqwest::StatusCode;
 mod utils;
+mod verify;
 mod versioning;
-use serde::Serialize;
 use tracing::{error, info, warn};
-use versioning::{JsonFileAsStruct, ManageTypes, ManagedExtensions, Metadata, Versioning};
+use versioning::{
+    Blocklist, JsonFileAsStruct, ManageTypes, ManagedExtensions, Metadata, Takedowns, Transfers,
+    Versioning,
+};
 
 fn main() -> ExitCode {
-    if let Ok(()) = run() {
-        info!("Exiting the tool");
-        ExitCode::from(0)
-    } else {
-        error!("Exiting the tool");
-        ExitCode::from(1)
+    match run() {
+        Ok(()) => {
+            info!("Exiting the tool");
+            ExitCode::from(0)
+        }
+        Err(err) => {
+            error!("{}", &err);
+            error!("Exiting the tool");
+            ExitCode::from(1)
+        }
     }
 }
 
-fn run() -> Result<(), ()> {
-    let request_client = initialization()?;
+fn run() -> Result<(), Error> {
+    let (command, backend) = initialization()?;
+    let backend = backend.as_ref();
+
+    match command {
+        Command::Sync(_) | Command::Diff(_) => sync_repository(backend),
+        Command::Verify => verify::run(backend),
+        Command::Repair(_) => repair::run(backend),
+        Command::Remove(_) => remove::run(backend),
+        Command::Promote(_) => promote::run(backend),
+    }
+}
+
+/*
+ * Syncs the extensions of the repository to the registry, retrying on top of the latest state of
+ * the registry when another run updated it in the meantime.
+ */
+fn sync_repository(backend: &dyn Backend) -> Result<(), Error> {
+    let dry_run = utils::env::dry_run();
+    if dry_run {
+        warn!("Dry run enabled, no changes will be written to the registry");
+    }
+
+    info!("Requesting the repository versioning file");
+    let repository_versioning = request_repository_versioning_file(backend)?;
+
+    info!("Listing the files in the repository");
+    let repository_files = registry::list_files(
+        backend,
+        &env::var("REPOSITORY").unwrap(),
+        &utils::env::repository_branch(),
+    )?;
+
+    registry::retry_on_conflict(|| {
+        sync(backend, &repository_versioning, &repository_files, dry_run)
+    })
+}
+
+/*
+ * Applies the repository versioning file on top of the latest state of the registry. Everything
+ * is read from the registry commit which was fetched first and the new commit is based on it, so
+ * if the registry moves in the meantime the reference update gets rejected instead of overwriting
+ * the other changes.
+ */
+fn sync(
+    backend: &dyn Backend,
+    repository_versioning: &Versioning,
+    repository_files: &BTreeMap<String, String>,
+    dry_run: bool,
+) -> Result<(), Error> {
+    info!("Fetching the latest commit and tree in the registry");
+    let registry_branch = backend.get_branch(
+        &utils::env::registry_repository(),
+        &utils::env::registry_branch(),
+    )?;
 
     info!("Requesting the registry versioning file");
     let (mut registry_versioning, mut registry_metadata, versioning_manage_type) =
-        request_registry_versioning_metadata_files(&request_client)?;
+        registry::request_registry_versioning_metadata_files(backend, &registry_branch.commit.sha)?;
 
-    info!("Requesting the repository versioning file");
-    let repository_versioning = request_repository_versioning_file(&request_client)?;
+    info!("Requesting the registry transfers file");
+    let mut registry_transfers = registry::request_optional_registry_file::<Transfers>(
+        backend,
+        &registry_branch.commit.sha,
+    )?;
+
+    info!("Requesting the registry takedowns file");
+    let registry_takedowns = registry::request_optional_registry_file::<Takedowns>(
+        backend,
+        &registry_branch.commit.sha,
+    )?;
+
+    info!("Requesting the registry blocklist file");
+    let registry_blocklist = registry::request_optional_registry_file::<Blocklist>(
+        backend,
+        &registry_branch.commit.sha,
+    )?;
+
+    let testing_versioning =
+        registry::request_testing_versioning_file(backend, &registry_branch.commit.sha)?;
+
+    info!("Listing the files in the registry");
+    let registry_files = match registry::list_files(
+        backend,
+        &utils::env::registry_repository(),
+        &registry_branch.commit.sha,
+    ) {
+        Ok(registry_files) => registry_files,
+        Err(err) if err.is_not_found() => BTreeMap::new(),
+        Err(err) => return Err(err),
+    };
 
     info!("Updating the local copy of the registry versioning and metadata files");
-    let mut managed_extensions =
-        registry_versioning.update(&mut registry_metadata, &repository_versioning)?;
+    let mut managed_extensions = registry_versioning.update(
+        &mut registry_metadata,
+        &mut registry_transfers,
+        (&registry_takedowns, &registry_blocklist),
+        repository_versioning,
+        testing_versioning.as_deref(),
+        (repository_files, &registry_files),
+    )?;
 
     if managed_extensions.is_empty() {
         return Ok(());
@@ -39,238 +150,276 @@ fn run() -> Result<(), ()> {
     info!(
         "Fetching the added and updated extensions from the repository and creating blobs for them in the registry"
     );
-    extension_management(&request_client, &mut managed_extensions)?;
+    extension_management(
+        backend,
+        &mut managed_extensions,
+        (repository_files, &registry_files),
+        dry_run,
+    )?;
 
     info!("Creating a blob from the local copy of the registry versioning file in the registry.");
-    create_registry_json_file_blob::<Versioning>(
-        &request_client,
+    registry::create_registry_json_file_blob::<Versioning>(
+        backend,
         &registry_versioning,
         &versioning_manage_type,
         "Versioning",
         &mut managed_extensions,
+        dry_run,
     )?;
 
     info!("Creating a blob from the local copy of the registry metadata file in the registry.");
-    create_registry_json_file_blob::<Metadata>(
-        &request_client,
+    registry::create_registry_json_file_blob::<Metadata>(
+        backend,
         &registry_metadata,
         &versioning_manage_type,
         "Metadata",
         &mut managed_extensions,
+        dry_run,
     )?;
 
-    info!("Fetching the latest commit and tree in the registry");
-    let registry_branch = request_client.get_branch(
-        &String::from("paperback-community/extensions"),
-        &String::from("master"),
-    )?;
-
-    info!("Creating a new tree in the registry");
-    let registry_update_tree = request_client.create_tree(
-        registry_branch.commit.commit.tree.sha.clone(),
-        managed_extensions,
-    )?;
-
-    info!("Creating a new commit in the registry");
-    let registry_update_commit = request_client.create_commit(
-        env::var("COMMIT_MESSAGE").unwrap_or_else(|_| {
-            format!(
-                "Registry management ({}, {})",
-                env::var("REPOSITORY")
-                    .unwrap()
-                    .strip_prefix("paperback-community/")
-                    .unwrap(),
-                env::var("BRANCH").unwrap(),
-            )
-        }),
-        registry_update_tree.sha,
-        registry_branch.commit.sha,
-        env::var("COMMIT_AUTHOR_NAME").unwrap_or_else(|_| String::from("github-actions[bot]")),
-        env::var("COMMIT_AUTHOR_EMAIL")
-            .unwrap_or_else(|_| String::from("github-actions[bot]@users.noreply.github.com")),
-    )?;
+    if managed_extensions
+        .iter()
+        .any(|managed_extension| managed_extension.1 == ManageTypes::Transfer)
+    {
+        info!("Removing the completed transfers from the registry transfers file");
+        if registry_transfers.is_empty() {
+            managed_extensions.push((
+                String::from("Transfers"),
+                ManageTypes::Deletion,
+                HashMap::from([(
+                    env::var("BRANCH").unwrap() + "/" + Transfers::FILE_NAME,
+                    None,
+                )]),
+            ));
+        } else {
+            registry::create_registry_json_file_blob::<Transfers>(
+                backend,
+                &registry_transfers,
+                &ManageTypes::Update,
+                "Transfers",
+                &mut managed_extensions,
+                dry_run,
+            )?;
+        }
+    }
 
-    info!("Updating the reference in the registry");
-    request_client.update_reference(registry_update_commit.sha)?;
+    if dry_run {
+        info!("Printing the changes which would have been made to the registry");
+        return registry::print_dry_run(
+            &managed_extensions,
+            &registry_files,
+            &registry_versioning,
+            &registry_metadata,
+        );
+    }
 
-    info!("Succesfully updated the registry");
-    Ok(())
+    registry::commit_registry(
+        backend,
+        &registry_branch,
+        managed_extensions,
+        format!(
+            "Registry management ({}, {})",
+            &utils::env::repository_name(),
+            env::var("BRANCH").unwrap(),
+        ),
+    )
 }
 
-fn initialization() -> Result<Requests, ()> {
+fn initialization() -> Result<(Command, Box<dyn Backend>), Error> {
     #[cfg(feature = "dotenv")]
     {
-        println!("Loading the .env file");
+        eprintln!("Loading the .env file");
         utils::env::load_dotenv()?;
     }
 
-    println!("Initializing the logger");
+    // The flags are exported before the logger reads LOG_LEVEL
+    let command = Cli::parse_and_export();
+
+    eprintln!("Initializing the logger");
     utils::logger::new()?;
 
-    info!("Validating the environment variables (REPOSITORY, BRANCH)");
+    info!("Validating the environment variables (REGISTRY_REPOSITORY, BACKEND)");
     utils::env::validate()?;
 
-    info!("Initializing the request client");
-    Requests::new()
-}
+    if let Command::Sync(_) | Command::Diff(_) = command {
+        info!("Validating the environment variables (REPOSITORY)");
+        utils::env::validate_repository()?;
+    }
 
-fn request_registry_versioning_metadata_files(
-    request_client: &Requests,
-) -> Result<(Box<Versioning>, Box<Metadata>, ManageTypes), ()> {
-    match request_client.get_file(
-        &String::from("paperback-community/extensions"),
-        &(env::var("BRANCH").unwrap() + "/versioning.json"),
-        &String::from("master"),
-        &FileOutputFormat::UTF8,
-    ) {
-        Ok(response) => {
-            if let Ok(registry_versioning) = Versioning::new(&response) {
-                info!("Requesting the registry metadata file");
-                if let Ok(response) = request_client.get_file(
-                    &String::from("paperback-community/extensions"),
-                    &(env::var("BRANCH").unwrap() + "/metadata.json"),
-                    &String::from("master"),
-                    &FileOutputFormat::UTF8,
-                ) {
-                    if let Ok(registry_metadata) = Metadata::new(&response) {
-                        return Ok((registry_versioning, registry_metadata, ManageTypes::Update));
-                    }
-                }
-            }
-        }
-        Err(false) => {
-            warn!(
-                "No registry versioning file found for this branch, assuming it's being created for the first time."
-            );
-            return Ok((
-                Box::new(Versioning::default()),
-                Box::new(Metadata::default()),
-                ManageTypes::Addition,
-            ));
-        }
-        Err(true) => (),
+    if let Command::Sync(_) | Command::Diff(_) | Command::Repair(_) | Command::Remove(_) = command {
+        info!("Validating the environment variables (BRANCH)");
+        utils::env::validate_branch()?;
+    }
+
+    if let Command::Promote(_) = command {
+        info!("Validating the environment variables (BRANCH)");
+        utils::env::validate_stable_branch()?;
     }
 
-    Err(())
+    if env::var("BACKEND").is_ok_and(|value| value == "local") {
+        info!("Initializing the local backend");
+        return Ok((
+            command,
+            Box::new(Local::new(PathBuf::from(env::var("LOCAL_PATH").unwrap()))?),
+        ));
+    }
+
+    info!("Initializing the request client");
+    Ok((command, Box::new(Requests::new()?)))
 }
 
-fn request_repository_versioning_file(request_client: &Requests) -> Result<Box<Versioning>, ()> {
-    if let Ok(response) = request_client.get_file(
+fn request_repository_versioning_file(backend: &dyn Backend) -> Result<Box<Versioning>, Error> {
+    let response = backend.get_file(
         &env::var("REPOSITORY").unwrap(),
         &(env::var("BRANCH").unwrap() + "/versioning.json"),
-        &String::from("gh-pages"),
+        &utils::env::repository_branch(),
         &FileOutputFormat::UTF8,
-    ) {
-        return Versioning::new(&response);
-    }
+    )?;
 
-    Err(())
+    Versioning::new(&response)
 }
 
+/*
+ * Fills in the files of every managed extension. The changed files of all extensions get fetched
+ * from the repository and turned into blobs in parallel.
+ */
 fn extension_management(
-    request_client: &Requests,
+    backend: &dyn Backend,
     managed_extensions: &mut ManagedExtensions,
-) -> Result<(), ()> {
-    for managed_extension in managed_extensions {
-        let (repository, branch) = match managed_extension.1 {
-            ManageTypes::Addition => {
-                info!("Adding extension: {}", managed_extension.0);
-                (&env::var("REPOSITORY").unwrap(), &String::from("gh-pages"))
-            }
-            ManageTypes::Update => {
-                info!("Updating extension: {}", managed_extension.0);
-                (&env::var("REPOSITORY").unwrap(), &String::from("gh-pages"))
-            }
-            ManageTypes::Deletion => {
-                info!("Deleting extension: {}", managed_extension.0);
-                (
-                    &String::from("paperback-community/extensions"),
-                    &String::from("master"),
-                )
-            }
-        };
+    (repository_files, registry_files): (&BTreeMap<String, String>, &BTreeMap<String, String>),
+    dry_run: bool,
+) -> Result<(), Error> {
+    let repository = env::var("REPOSITORY").unwrap();
+    let repository_branch = utils::env::repository_branch();
 
-        if managed_extension.1 == ManageTypes::Deletion {
-            managed_extension.2.insert(
-                env::var("BRANCH").unwrap() + "/" + &managed_extension.0 + "/index.js",
-                None,
-            );
-        } else if let Ok(response) = request_client.get_file(
-            &env::var("REPOSITORY").unwrap(),
-            &(env::var("BRANCH").unwrap() + "/" + &managed_extension.0 + "/index.js"),
-            &String::from("gh-pages"),
-            &FileOutputFormat::UTF8,
-        ) {
-            if let Ok(blob) = request_client.create_blob(response, String::from("utf-8")) {
-                managed_extension.2.insert(
-                    env::var("BRANCH").unwrap() + "/" + &managed_extension.0 + "/index.js",
-                    Some(blob.sha),
-                );
-            } else {
-                return Err(());
-            }
-        } else {
-            return Err(());
+    let mut uploads = vec![];
+    for (index, managed_extension) in managed_extensions.iter_mut().enumerate() {
+        match managed_extension.1 {
+            ManageTypes::Addition => info!("Adding extension: {}", managed_extension.0),
+            ManageTypes::Update => info!("Updating extension: {}", managed_extension.0),
+            ManageTypes::Rollback => info!("Rolling back extension: {}", managed_extension.0),
+            ManageTypes::Transfer => info!("Transferring extension: {}", managed_extension.0),
+            ManageTypes::Deletion => info!("Deleting extension: {}", managed_extension.0),
         }
 
-        if let Ok(response) = request_client.get_directory(
-            repository,
-            &(env::var("BRANCH").unwrap() + "/" + &managed_extension.0 + "/static"),
-            branch,
-        ) {
-            for file in response {
-                if file.etype != "file" {
-                    continue;
-                }
+        let changes = extension_file_changes(
+            &managed_extension.0,
+            &managed_extension.1,
+            repository_files,
+            registry_files,
+            (&repository, &repository_branch),
+        )
+        .map_err(|err| Error::Extension {
+            id: managed_extension.0.clone(),
+            error: Box::new(err),
+        })?;
 
-                if managed_extension.1 == ManageTypes::Deletion {
-                    managed_extension.2.insert(file.path.clone(), None);
-                } else if let Ok(response) = request_client.get_file(
-                    &env::var("REPOSITORY").unwrap(),
-                    &file.path,
-                    &String::from("gh-pages"),
-                    &FileOutputFormat::BASE64,
-                ) {
-                    if let Ok(blob) = request_client.create_blob(response, String::from("base64")) {
-                        managed_extension.2.insert(file.path, Some(blob.sha));
-                    } else {
-                        return Err(());
-                    }
-                } else {
-                    return Err(());
+        for (path, output_format) in changes {
+            match output_format {
+                Some(output_format) => uploads.push((index, path, output_format)),
+                None => {
+                    managed_extension.2.insert(path, None);
                 }
             }
-        } else {
-            return Err(());
         }
     }
 
+    let blob_shas = utils::parallel::map(
+        &uploads,
+        utils::env::concurrency(),
+        |(index, path, output_format)| {
+            upload_extension_file(backend, &repository, path, output_format, dry_run).map_err(
+                |err| Error::Extension {
+                    id: managed_extensions[*index].0.clone(),
+                    error: Box::new(err),
+                },
+            )
+        },
+    )?;
+
+    for ((index, path, _), blob_sha) in uploads.into_iter().zip(blob_shas) {
+        managed_extensions[index].2.insert(path, Some(blob_sha));
+    }
+
     Ok(())
 }
 
-fn create_registry_json_file_blob<JFAS: JsonFileAsStruct + Serialize>(
-    request_client: &Requests,
-    registry_versioning: &JFAS,
-    versioning_manage_type: &ManageTypes,
-    name: &str,
-    managed_extensions: &mut ManagedExtensions,
-) -> Result<(), ()> {
-    if let Ok(registry_versioning_string) = registry_versioning.to_utf8() {
-        if let Ok(blob) =
-            request_client.create_blob(registry_versioning_string, String::from("utf-8"))
-        {
-            managed_extensions.push((
-                name.to_string(),
-                versioning_manage_type.clone(),
-                HashMap::from([(
-                    env::var("BRANCH").unwrap() + "/" + name.to_lowercase().as_str() + ".json",
-                    Some(blob.sha),
-                )]),
-            ));
+/*
+ * Determines which files of the extension have to change in the registry, together with the
+ * format they should be fetched in. Files of deleted extensions don't have to be fetched. Files of
+ * updated and transferred extensions which are identical in the registry are left out, and the
+ * ones which are no longer part of the extension get deleted.
+ */
+fn extension_file_changes(
+    extension: &str,
+    manage_type: &ManageTypes,
+    repository_files: &BTreeMap<String, String>,
+    registry_files: &BTreeMap<String, String>,
+    (repository, repository_branch): (&str, &str),
+) -> Result<Vec<(String, Option<FileOutputFormat>)>, Error> {
+    let registry_files = registry::extension_files(registry_files, extension);
+
+    if *manage_type == ManageTypes::Deletion {
+        return Ok(registry_files
+            .into_keys()
+            .map(|path| (path.clone(), None))
+            .collect());
+    }
+
+    let index_path = env::var("BRANCH").unwrap() + "/" + extension + "/index.js";
+
+    let files = registry::extension_files(repository_files, extension);
+    if !files.contains_key(&index_path) {
+        return Err(Error::Status {
+            path: format!("{repository}/{index_path}@{repository_branch}"),
+            status: StatusCode::NOT_FOUND,
+        });
+    }
 
-            return Ok(());
+    let mut changes = vec![];
+    for (path, sha) in &files {
+        if *manage_type != ManageTypes::Addition && registry_files.get(path) == Some(sha) {
+            continue;
+        }
+
+        let output_format = if **path == index_path {
+            FileOutputFormat::UTF8
+        } else {
+            FileOutputFormat::BASE64
+        };
+        changes.push(((*path).clone(), Some(output_format)));
+    }
+
+    // Files which were dropped from the extension would otherwise stay in the registry forever
+    if *manage_type != ManageTypes::Addition {
+        for path in registry_files.into_keys() {
+            if !files.contains_key(path) {
+                changes.push((path.clone(), None));
+            }
         }
     }
 
-    Err(())
+    Ok(changes)
+}
+
+fn upload_extension_file(
+    backend: &dyn Backend,
+    repository: &str,
+    path: &str,
+    output_format: &FileOutputFormat,
+    dry_run: bool,
+) -> Result<String, Error> {
+    let response = backend.get_file(
+        repository,
+        path,
+        &utils::env::repository_branch(),
+        output_format,
+    )?;
+
+    let encoding = match output_format {
+        FileOutputFormat::UTF8 => "utf-8",
+        FileOutputFormat::BASE64 => "base64",
+    };
+
+    registry::create_blob(backend, response, encoding, dry_run)
 }
@@ -0,0 +1,205 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    backend::Backend,
+    error::Error,
+    registry,
+    requests::FileOutputFormat,
+    utils,
+    versioning::{Blocklist, JsonFileAsStruct, ManageTypes, Metadata, Takedowns, Versioning},
+};
+
+/*
+ * Promotes extensions from the testing branch to the stable branch within the registry, the files
+ * of the extensions are reused from the testing branch so nothing has to be uploaded. The
+ * extensions are promoted together, when one of them can't be promoted none of them are.
+ */
+pub fn run(backend: &dyn Backend) -> Result<(), Error> {
+    let dry_run = utils::env::dry_run();
+    if dry_run {
+        warn!("Dry run enabled, no changes will be written to the registry");
+    }
+
+    registry::retry_on_conflict(|| promote(backend, dry_run))
+}
+
+fn promote(backend: &dyn Backend, dry_run: bool) -> Result<(), Error> {
+    let registry_repository = utils::env::registry_repository();
+    let branch = env::var("BRANCH").unwrap();
+    let testing_branch = utils::env::testing_branch().unwrap();
+    let ids = utils::env::extension_ids();
+
+    info!("Fetching the latest commit and tree in the registry");
+    let registry_branch =
+        backend.get_branch(&registry_repository, &utils::env::registry_branch())?;
+    let registry_commit = &registry_branch.commit.sha;
+
+    info!("Requesting the registry versioning file of the testing branch");
+    let mut testing_versioning = Versioning::new(&backend.get_file(
+        &registry_repository,
+        &format!("{testing_branch}/{}", Versioning::FILE_NAME),
+        registry_commit,
+        &FileOutputFormat::UTF8,
+    )?)?;
+
+    info!("Requesting the registry metadata file of the testing branch");
+    let testing_metadata = Metadata::new(&backend.get_file(
+        &registry_repository,
+        &format!("{testing_branch}/{}", Metadata::FILE_NAME),
+        registry_commit,
+        &FileOutputFormat::UTF8,
+    )?)?;
+
+    info!("Requesting the registry versioning file");
+    let (mut registry_versioning, mut registry_metadata, versioning_manage_type) =
+        registry::request_registry_versioning_metadata_files(backend, registry_commit)?;
+
+    info!("Requesting the registry takedowns file");
+    let registry_takedowns =
+        registry::request_optional_registry_file::<Takedowns>(backend, registry_commit)?;
+
+    info!("Requesting the registry blocklist file");
+    let registry_blocklist =
+        registry::request_optional_registry_file::<Blocklist>(backend, registry_commit)?;
+
+    info!("Listing the files in the registry");
+    let testing_files = registry::list_branch_files(
+        backend,
+        &registry_repository,
+        registry_commit,
+        &testing_branch,
+    )?;
+    let registry_files = registry::list_files(backend, &registry_repository, registry_commit)?;
+
+    info!("Updating the local copy of the registry versioning and metadata files");
+    let mut managed_extensions = vec![];
+    for id in &ids {
+        let promoted_files = promoted_files(&testing_files, (&testing_branch, &branch), id);
+
+        let Some(manage_type) = registry_versioning.promote(
+            &mut registry_metadata,
+            (&testing_versioning, &testing_metadata),
+            (&registry_takedowns, &registry_blocklist),
+            &promoted_files,
+            id,
+        )?
+        else {
+            continue;
+        };
+
+        info!("Promoting extension: {}", id);
+        let stable_files = registry::extension_files(&registry_files, id);
+        let mut files = promoted_files
+            .iter()
+            .filter(|(path, sha)| stable_files.get(path) != Some(sha))
+            .map(|(path, sha)| (path.clone(), Some(sha.clone())))
+            .collect::<HashMap<String, Option<String>>>();
+        // Files which are no longer part of the extension on the testing branch
+        for path in stable_files.into_keys() {
+            if !promoted_files.contains_key(path) {
+                files.insert(path.clone(), None);
+            }
+        }
+
+        managed_extensions.push((id.clone(), manage_type, files));
+    }
+
+    if managed_extensions.is_empty() {
+        warn!("There are no extensions to promote");
+        return Ok(());
+    }
+
+    let promoted_ids = managed_extensions
+        .iter()
+        .map(|managed_extension| managed_extension.0.as_str())
+        .collect::<Vec<&str>>()
+        .join(", ");
+
+    /*
+     * The promoted extensions stay on the testing branch for the next testing round, its
+     * versioning file is only restamped to record when extensions were last promoted from it.
+     */
+    info!("Creating blobs from the versioning and metadata files of the testing branch.");
+    testing_versioning.restamp();
+    registry::create_branch_json_file_blob::<Versioning>(
+        backend,
+        &testing_versioning,
+        &ManageTypes::Update,
+        ("Versioning", &testing_branch),
+        &mut managed_extensions,
+        dry_run,
+    )?;
+    registry::create_branch_json_file_blob::<Metadata>(
+        backend,
+        &testing_metadata,
+        &ManageTypes::Update,
+        ("Metadata", &testing_branch),
+        &mut managed_extensions,
+        dry_run,
+    )?;
+
+    info!("Creating a blob from the local copy of the registry versioning file in the registry.");
+    registry::create_registry_json_file_blob::<Versioning>(
+        backend,
+        &registry_versioning,
+        &versioning_manage_type,
+        "Versioning",
+        &mut managed_extensions,
+        dry_run,
+    )?;
+
+    info!("Creating a blob from the local copy of the registry metadata file in the registry.");
+    registry::create_registry_json_file_blob::<Metadata>(
+        backend,
+        &registry_metadata,
+        &versioning_manage_type,
+        "Metadata",
+        &mut managed_extensions,
+        dry_run,
+    )?;
+
+    if dry_run {
+        info!("Printing the changes which would have been made to the registry");
+        return registry::print_dry_run(
+            &managed_extensions,
+            &registry_files,
+            &registry_versioning,
+            &registry_metadata,
+        );
+    }
+
+    registry::commit_registry(
+        backend,
+        &registry_branch,
+        managed_extensions,
+        format!("Registry promotion ({promoted_ids}, {testing_branch} -> {branch})"),
+    )
+}
+
+/*
+ * The files the extension has on the testing branch, at the paths they get on the stable branch
+ * with the SHAs of their existing blobs.
+ */
+fn promoted_files(
+    testing_files: &BTreeMap<String, String>,
+    (testing_branch, branch): (&str, &str),
+    id: &str,
+) -> BTreeMap<String, String> {
+    let testing_prefix = format!("{testing_branch}/{id}/");
+
+    testing_files
+        .range(testing_prefix.clone()..)
+        .take_while(|(path, _)| path.starts_with(&testing_prefix))
+        .map(|(path, sha)| {
+            (
+                format!("{branch}/{id}/{}", &path[testing_prefix.len()..]),
+                sha.clone(),
+            )
+        })
+        .collect()
+}
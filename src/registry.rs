@@ -0,0 +1,312 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    backend::Backend,
+    error::Error,
+    requests::{FileOutputFormat, GetBranchResponse},
+    utils,
+    versioning::{JsonFileAsStruct, ManageTypes, ManagedExtensions, Metadata, Versioning},
+};
+
+/*
+ * Runs the operation again when the registry was updated by another run before the operation
+ * could commit its changes.
+ */
+pub fn retry_on_conflict(mut operation: impl FnMut() -> Result<(), Error>) -> Result<(), Error> {
+    let commit_retries = utils::env::commit_retries();
+    let mut attempt = 0;
+    loop {
+        match operation() {
+            Err(Error::NotFastForward { .. }) if attempt < commit_retries => {
+                attempt += 1;
+                warn!(
+                    "The registry was updated by another run in the meantime, retrying with its latest state ({}/{})",
+                    attempt, commit_retries
+                );
+            }
+            result => return result,
+        }
+    }
+}
+
+/*
+ * Commits the managed files on top of the given registry commit and moves the registry branch to
+ * it, COMMIT_MESSAGE overrides the given default message.
+ */
+pub fn commit_registry(
+    backend: &dyn Backend,
+    registry_branch: &GetBranchResponse,
+    managed_extensions: ManagedExtensions,
+    default_message: String,
+) -> Result<(), Error> {
+    info!("Creating a new tree in the registry");
+    let registry_update_tree = backend.create_tree(
+        registry_branch.commit.commit.tree.sha.clone(),
+        managed_extensions,
+    )?;
+
+    info!("Creating a new commit in the registry");
+    let registry_update_commit = backend.create_commit(
+        env::var("COMMIT_MESSAGE").unwrap_or(default_message),
+        registry_update_tree.sha,
+        registry_branch.commit.sha.clone(),
+        env::var("COMMIT_AUTHOR_NAME").unwrap_or_else(|_| String::from("github-actions[bot]")),
+        env::var("COMMIT_AUTHOR_EMAIL")
+            .unwrap_or_else(|_| String::from("github-actions[bot]@users.noreply.github.com")),
+    )?;
+
+    info!("Updating the reference in the registry");
+    backend.update_reference(registry_update_commit.sha)?;
+
+    info!("Succesfully updated the registry");
+    Ok(())
+}
+
+pub fn request_registry_versioning_metadata_files(
+    backend: &dyn Backend,
+    registry_commit: &str,
+) -> Result<(Box<Versioning>, Box<Metadata>, ManageTypes), Error> {
+    match backend.get_file(
+        &utils::env::registry_repository(),
+        &(env::var("BRANCH").unwrap() + "/versioning.json"),
+        registry_commit,
+        &FileOutputFormat::UTF8,
+    ) {
+        Ok(response) => {
+            let registry_versioning = Versioning::new(&response)?;
+
+            info!("Requesting the registry metadata file");
+            let response = backend.get_file(
+                &utils::env::registry_repository(),
+                &(env::var("BRANCH").unwrap() + "/metadata.json"),
+                registry_commit,
+                &FileOutputFormat::UTF8,
+            )?;
+
+            Ok((
+                registry_versioning,
+                Metadata::new(&response)?,
+                ManageTypes::Update,
+            ))
+        }
+        Err(err) if err.is_not_found() => {
+            warn!(
+                "No registry versioning file found for this branch, assuming it's being created for the first time."
+            );
+            Ok((
+                Box::new(Versioning::default()),
+                Box::new(Metadata::default()),
+                ManageTypes::Addition,
+            ))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/*
+ * The transfers, takedowns and blocklist files are optional, without them no extensions are
+ * transferred, taken down or blocked.
+ */
+pub fn request_optional_registry_file<JFAS>(
+    backend: &dyn Backend,
+    registry_commit: &str,
+) -> Result<Box<JFAS>, Error>
+where
+    JFAS: JsonFileAsStruct + Default + for<'de> Deserialize<'de>,
+{
+    match backend.get_file(
+        &utils::env::registry_repository(),
+        &(env::var("BRANCH").unwrap() + "/" + JFAS::FILE_NAME),
+        registry_commit,
+        &FileOutputFormat::UTF8,
+    ) {
+        Ok(response) => JFAS::new(&response),
+        Err(err) if err.is_not_found() => Ok(Box::new(JFAS::default())),
+        Err(err) => Err(err),
+    }
+}
+
+/*
+ * Additions to a stable branch are checked against the versioning file of its testing branch.
+ * Without a testing branch for this version none of the extensions were tested, so no additions
+ * are accepted.
+ */
+pub fn request_testing_versioning_file(
+    backend: &dyn Backend,
+    registry_commit: &str,
+) -> Result<Option<Box<Versioning>>, Error> {
+    let Some(testing_branch) = utils::env::testing_branch() else {
+        return Ok(None);
+    };
+
+    info!("Requesting the registry versioning file of the testing branch");
+    match backend.get_file(
+        &utils::env::registry_repository(),
+        &format!("{testing_branch}/{}", Versioning::FILE_NAME),
+        registry_commit,
+        &FileOutputFormat::UTF8,
+    ) {
+        Ok(response) => Ok(Some(Versioning::new(&response)?)),
+        Err(err) if err.is_not_found() => {
+            warn!(
+                "No registry versioning file found for {}, extensions can't be added to the stable branch",
+                testing_branch
+            );
+            Ok(Some(Box::new(Versioning::default())))
+        }
+        Err(err) => Err(err),
+    }
+}
+
+/*
+ * Lists every file in the directory of the branch, mapped from their path to their blob SHA.
+ */
+pub fn list_files(
+    backend: &dyn Backend,
+    repository: &str,
+    reference: &str,
+) -> Result<BTreeMap<String, String>, Error> {
+    list_branch_files(backend, repository, reference, &env::var("BRANCH").unwrap())
+}
+
+pub fn list_branch_files(
+    backend: &dyn Backend,
+    repository: &str,
+    reference: &str,
+    branch: &str,
+) -> Result<BTreeMap<String, String>, Error> {
+    let response = backend.get_tree(repository, reference)?;
+    if response.truncated {
+        return Err(Error::TruncatedTree {
+            path: format!("{repository}@{reference}"),
+        });
+    }
+
+    let prefix = branch.to_string() + "/";
+
+    Ok(response
+        .tree
+        .into_iter()
+        .filter(|entry| entry.etype == "blob" && entry.path.starts_with(&prefix))
+        .map(|entry| (entry.path, entry.sha))
+        .collect())
+}
+
+/*
+ * Selects the files which belong to the extension, every file below its directory.
+ */
+pub fn extension_files<'f>(
+    files: &'f BTreeMap<String, String>,
+    extension: &str,
+) -> BTreeMap<&'f String, &'f String> {
+    utils::git::directory_files(files, &(env::var("BRANCH").unwrap() + "/" + extension))
+}
+
+pub fn create_blob(
+    backend: &dyn Backend,
+    content: String,
+    encoding: &str,
+    dry_run: bool,
+) -> Result<String, Error> {
+    if dry_run {
+        return Ok(utils::git::blob_sha(&utils::git::decode(
+            &content, encoding,
+        )?));
+    }
+
+    Ok(backend.create_blob(content, encoding.to_string())?.sha)
+}
+
+pub fn create_registry_json_file_blob<JFAS: JsonFileAsStruct + Serialize>(
+    backend: &dyn Backend,
+    registry_versioning: &JFAS,
+    versioning_manage_type: &ManageTypes,
+    name: &str,
+    managed_extensions: &mut ManagedExtensions,
+    dry_run: bool,
+) -> Result<(), Error> {
+    create_branch_json_file_blob(
+        backend,
+        registry_versioning,
+        versioning_manage_type,
+        (name, &env::var("BRANCH").unwrap()),
+        managed_extensions,
+        dry_run,
+    )
+}
+
+pub fn create_branch_json_file_blob<JFAS: JsonFileAsStruct + Serialize>(
+    backend: &dyn Backend,
+    registry_versioning: &JFAS,
+    versioning_manage_type: &ManageTypes,
+    (name, branch): (&str, &str),
+    managed_extensions: &mut ManagedExtensions,
+    dry_run: bool,
+) -> Result<(), Error> {
+    let blob_sha = create_blob(backend, registry_versioning.to_utf8()?, "utf-8", dry_run)?;
+
+    managed_extensions.push((
+        name.to_string(),
+        versioning_manage_type.clone(),
+        HashMap::from([(
+            branch.to_string() + "/" + name.to_lowercase().as_str() + ".json",
+            Some(blob_sha),
+        )]),
+    ));
+
+    Ok(())
+}
+
+/*
+ * Prints the paths the commit would add, change or delete, where a path is added when the
+ * registry doesn't have a file at it yet.
+ */
+pub fn print_dry_run(
+    managed_extensions: &ManagedExtensions,
+    registry_files: &BTreeMap<String, String>,
+    registry_versioning: &Versioning,
+    registry_metadata: &Metadata,
+) -> Result<(), Error> {
+    let mut changes = managed_extensions
+        .iter()
+        .flat_map(|managed_extension| {
+            managed_extension.2.iter().map(|(path, sha)| {
+                let action = match sha {
+                    None => "delete",
+                    Some(_) if registry_files.contains_key(path) => "change",
+                    Some(_) => "add",
+                };
+                (path, action, sha.as_deref().unwrap_or("-"))
+            })
+        })
+        .collect::<Vec<(&String, &str, &str)>>();
+    changes.sort_unstable();
+
+    println!(
+        "Changes which would be committed to {} ({}):",
+        utils::env::registry_repository(),
+        utils::env::registry_branch()
+    );
+    for (path, action, sha) in changes {
+        println!("{action:<6}  {path}  {sha}");
+    }
+
+    println!(
+        "\n{}/versioning.json:\n{}",
+        env::var("BRANCH").unwrap(),
+        registry_versioning.to_utf8()?
+    );
+    println!(
+        "\n{}/metadata.json:\n{}",
+        env::var("BRANCH").unwrap(),
+        registry_metadata.to_utf8()?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, env};
+
+use tracing::{info, warn};
+
+use crate::{
+    backend::Backend,
+    error::Error,
+    registry, utils,
+    versioning::{ManageTypes, Metadata, Takedowns, Versioning},
+};
+
+/*
+ * Takes an extension down from the branch without involving the repository it belongs to, and
+ * records the takedown so later syncs don't add it again.
+ */
+pub fn run(backend: &dyn Backend) -> Result<(), Error> {
+    let dry_run = utils::env::dry_run();
+    if dry_run {
+        warn!("Dry run enabled, no changes will be written to the registry");
+    }
+
+    registry::retry_on_conflict(|| remove(backend, dry_run))
+}
+
+fn remove(backend: &dyn Backend, dry_run: bool) -> Result<(), Error> {
+    let registry_repository = utils::env::registry_repository();
+    let branch = env::var("BRANCH").unwrap();
+    let id = env::var("EXTENSION_ID").unwrap();
+
+    info!("Fetching the latest commit and tree in the registry");
+    let registry_branch =
+        backend.get_branch(&registry_repository, &utils::env::registry_branch())?;
+    let registry_commit = &registry_branch.commit.sha;
+
+    info!("Requesting the registry versioning file");
+    let (mut registry_versioning, mut registry_metadata, _) =
+        registry::request_registry_versioning_metadata_files(backend, registry_commit)?;
+
+    info!("Requesting the registry takedowns file");
+    let mut registry_takedowns =
+        registry::request_optional_registry_file::<Takedowns>(backend, registry_commit)?;
+
+    info!("Updating the local copy of the registry versioning and metadata files");
+    let owner = registry_metadata.owner(&id).cloned();
+    if let Some(owner) = &owner {
+        registry_metadata.remove(owner, &id);
+    }
+    let in_registry = registry_versioning.remove_source(&id) || owner.is_some();
+
+    let taken_down =
+        registry_takedowns.insert(&id, owner.clone(), env::var("TAKEDOWN_REASON").ok());
+
+    if !in_registry {
+        if !taken_down {
+            info!("The {} extension was already taken down", id);
+            return Ok(());
+        }
+
+        warn!(
+            "The {} extension is not part of the registry, only recording its takedown",
+            id
+        );
+    }
+
+    info!("Taking down extension: {}", id);
+    info!("Listing the files in the registry");
+    let registry_files = registry::list_files(backend, &registry_repository, registry_commit)?;
+
+    let mut managed_extensions = vec![(
+        id.clone(),
+        ManageTypes::Deletion,
+        registry::extension_files(&registry_files, &id)
+            .into_keys()
+            .map(|path| (path.clone(), None))
+            .collect::<HashMap<String, Option<String>>>(),
+    )];
+
+    if in_registry {
+        info!(
+            "Creating a blob from the local copy of the registry versioning file in the registry."
+        );
+        registry::create_registry_json_file_blob::<Versioning>(
+            backend,
+            &registry_versioning,
+            &ManageTypes::Update,
+            "Versioning",
+            &mut managed_extensions,
+            dry_run,
+        )?;
+
+        info!("Creating a blob from the local copy of the registry metadata file in the registry.");
+        registry::create_registry_json_file_blob::<Metadata>(
+            backend,
+            &registry_metadata,
+            &ManageTypes::Update,
+            "Metadata",
+            &mut managed_extensions,
+            dry_run,
+        )?;
+    }
+
+    if taken_down {
+        info!(
+            "Creating a blob from the local copy of the registry takedowns file in the registry."
+        );
+        registry::create_registry_json_file_blob::<Takedowns>(
+            backend,
+            &registry_takedowns,
+            &ManageTypes::Update,
+            "Takedowns",
+            &mut managed_extensions,
+            dry_run,
+        )?;
+    }
+
+    if dry_run {
+        info!("Printing the changes which would have been made to the registry");
+        return registry::print_dry_run(
+            &managed_extensions,
+            &registry_files,
+            &registry_versioning,
+            &registry_metadata,
+        );
+    }
+
+    registry::commit_registry(
+        backend,
+        &registry_branch,
+        managed_extensions,
+        format!("Registry takedown ({id}, {branch})"),
+    )
+}
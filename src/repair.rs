@@ -0,0 +1,190 @@
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    env,
+};
+
+use tracing::{info, warn};
+
+use crate::{
+    backend::Backend,
+    error::Error,
+    registry,
+    requests::FileOutputFormat,
+    utils,
+    versioning::{JsonFileAsStruct, ManageTypes, Metadata, MetadataExtension, Versioning},
+};
+
+/*
+ * Rebuilds the metadata file of the branch from the versioning files of the extension
+ * repositories, for when it got lost or corrupted.
+ */
+pub fn run(backend: &dyn Backend) -> Result<(), Error> {
+    let dry_run = utils::env::dry_run();
+    if dry_run {
+        warn!("Dry run enabled, no changes will be written to the registry");
+    }
+
+    registry::retry_on_conflict(|| repair(backend, dry_run))
+}
+
+fn repair(backend: &dyn Backend, dry_run: bool) -> Result<(), Error> {
+    let registry_repository = utils::env::registry_repository();
+    let branch = env::var("BRANCH").unwrap();
+
+    info!("Fetching the latest commit and tree in the registry");
+    let registry_branch =
+        backend.get_branch(&registry_repository, &utils::env::registry_branch())?;
+
+    info!("Requesting the registry versioning file");
+    let registry_versioning = Versioning::new(&backend.get_file(
+        &registry_repository,
+        &format!("{branch}/{}", Versioning::FILE_NAME),
+        &registry_branch.commit.sha,
+        &FileOutputFormat::UTF8,
+    )?)?;
+
+    info!("Requesting the registry metadata file");
+    let previous_metadata = match backend
+        .get_file(
+            &registry_repository,
+            &format!("{branch}/{}", Metadata::FILE_NAME),
+            &registry_branch.commit.sha,
+            &FileOutputFormat::UTF8,
+        )
+        .and_then(|response| Metadata::new(&response))
+    {
+        Ok(metadata) => Some(metadata),
+        // Other failures don't say anything about the file, rebuilding it would lose its contents
+        Err(err) if err.is_not_found() || matches!(err, Error::Deserialize { .. }) => {
+            warn!(
+                "The registry metadata file can't be used, rebuilding it from scratch: {}",
+                err
+            );
+            None
+        }
+        Err(err) => return Err(err),
+    };
+
+    // The repositories in the previous metadata file are known to publish to the registry
+    let mut repositories = utils::env::repositories();
+    if let Some(previous_metadata) = &previous_metadata {
+        repositories.extend(previous_metadata.repositories().cloned());
+    }
+    repositories.sort_unstable();
+    repositories.dedup();
+
+    if repositories.is_empty() {
+        return Err(Error::Environment {
+            variable: String::from("REPOSITORIES"),
+            message: String::from(
+                "it should list the extension repositories when there is no usable metadata file",
+            ),
+        });
+    }
+
+    let source_ids = registry_versioning
+        .source_ids()
+        .collect::<BTreeSet<&String>>();
+
+    let mut claims = BTreeMap::<&String, Vec<(&String, MetadataExtension)>>::new();
+    for repository in &repositories {
+        info!(
+            "Requesting the versioning file of the {} repository",
+            repository
+        );
+        let repository_versioning = match backend
+            .get_file(
+                &format!("{}/{}", utils::env::owner(), repository),
+                &format!("{branch}/{}", Versioning::FILE_NAME),
+                &utils::env::repository_branch(),
+                &FileOutputFormat::UTF8,
+            )
+            .and_then(|response| Versioning::new(&response))
+        {
+            Ok(repository_versioning) => repository_versioning,
+            Err(err) if err.is_not_found() => {
+                warn!(
+                    "The {} repository has no versioning file for this branch, skipping it",
+                    repository
+                );
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        for id in repository_versioning.source_ids() {
+            let Some(id) = source_ids.get(id) else {
+                continue;
+            };
+
+            // Keep the build information of extensions which were already known
+            let extension = previous_metadata
+                .as_ref()
+                .and_then(|previous_metadata| previous_metadata.extension(repository, id))
+                .cloned()
+                .unwrap_or_else(|| MetadataExtension::new(&repository_versioning));
+
+            claims.entry(id).or_default().push((repository, extension));
+        }
+    }
+
+    let mut metadata = Metadata::default();
+    for id in source_ids {
+        let mut claim = claims.remove(id).unwrap_or_default();
+
+        // Extensions provided by multiple repositories stay with the repository they belonged to
+        if claim.len() > 1 {
+            claim.retain(|(repository, _)| {
+                previous_metadata.as_ref().is_some_and(|previous_metadata| {
+                    previous_metadata.extension(repository, id).is_some()
+                })
+            });
+
+            if claim.len() != 1 {
+                warn!(
+                    "The {} extension is provided by multiple repositories, leaving it without an owner",
+                    id
+                );
+                continue;
+            }
+        }
+
+        match claim.pop() {
+            Some((repository, extension)) => metadata.insert(repository, id, extension),
+            None => warn!(
+                "The {} extension is not provided by any repository, leaving it without an owner",
+                id
+            ),
+        }
+    }
+
+    let metadata_path = format!("{branch}/{}", Metadata::FILE_NAME);
+    let content = metadata.to_utf8()?;
+
+    if let Some(previous_metadata) = &previous_metadata
+        && previous_metadata.to_utf8()? == content
+    {
+        info!("The registry metadata file is already correct");
+        return Ok(());
+    }
+
+    if dry_run {
+        info!("Printing the metadata file which would have been committed to the registry");
+        println!("{metadata_path}:\n{content}");
+        return Ok(());
+    }
+
+    info!("Creating a blob from the rebuilt registry metadata file in the registry");
+    let blob_sha = backend.create_blob(content, String::from("utf-8"))?.sha;
+
+    registry::commit_registry(
+        backend,
+        &registry_branch,
+        vec![(
+            String::from("Metadata"),
+            ManageTypes::Update,
+            HashMap::from([(metadata_path, Some(blob_sha))]),
+        )],
+        format!("Registry repair ({branch})"),
+    )
+}
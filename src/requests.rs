@@ -1,27 +1,42 @@
-use std::{env, time::Duration};
+use std::{
+    env, thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 use base64::{Engine, prelude::BASE64_STANDARD};
 use reqwest::{
     StatusCode,
-    blocking::Client,
+    blocking::{Client, RequestBuilder, Response},
     header::{HeaderMap, HeaderValue},
 };
-use serde::{Deserialize, Serialize};
-use tracing::error;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use tracing::warn;
 
-use crate::versioning;
+use crate::{backend::Backend, error::Error, utils, versioning};
 use versioning::ManagedExtensions;
 
+// The longest delay between two retries of a request which failed with a server error
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+// How long GitHub asks to wait on a secondary rate limit which did not say when it resets
+const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(60);
+
 pub enum FileOutputFormat {
     UTF8,
     BASE64,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct GetContentDirectoryEntryResponse {
+pub struct GetTreeResponse {
+    pub tree: Vec<GetTreeEntryResponse>,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GetTreeEntryResponse {
+    pub path: String,
     #[serde(rename = "type")]
     pub etype: String,
-    pub path: String,
+    pub sha: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -98,14 +113,21 @@ pub struct CreateCommitResponse {
 #[derive(Debug, Serialize)]
 struct UpdateReferenceRequest {
     sha: String,
+    force: bool,
 }
 
 pub struct Requests {
     client: Client,
+    api_url: String,
+    registry_repository: String,
+    registry_branch: String,
+    retries: u32,
+    backoff: Duration,
+    rate_limit_max_wait: Duration,
 }
 
 impl Requests {
-    pub fn new() -> Result<Requests, ()> {
+    pub fn new() -> Result<Requests, Error> {
         let mut headers = HeaderMap::new();
         headers.insert(
             "Accept",
@@ -115,223 +137,259 @@ impl Requests {
             "X-GitHub-Api-Version",
             HeaderValue::from_str("2022-11-28").unwrap(),
         );
+        let token = env::var("GITHUB_TOKEN").map_err(|_| Error::Environment {
+            variable: String::from("GITHUB_TOKEN"),
+            message: String::from("it was not set"),
+        })?;
         headers.insert(
             "Authorization",
-            HeaderValue::from_str(format!("Bearer {}", env::var("GITHUB_TOKEN").unwrap()).as_str())
-                .unwrap(),
+            HeaderValue::from_str(format!("Bearer {token}").as_str()).map_err(|err| {
+                Error::Environment {
+                    variable: String::from("GITHUB_TOKEN"),
+                    message: err.to_string(),
+                }
+            })?,
         );
 
         match Client::builder()
             .user_agent("paperback-community/registry-manager")
             .default_headers(headers)
-            .timeout(Duration::new(15, 0))
+            .timeout(utils::env::http_timeout())
             .build()
         {
-            Ok(client) => Ok(Requests { client }),
-            Err(err) => {
-                error!(
-                    "Something went wrong while creating the request client: {}",
-                    &err
-                );
-                Err(())
-            }
+            Ok(client) => Ok(Requests {
+                client,
+                api_url: env::var("GITHUB_API_URL")
+                    .unwrap_or_else(|_| String::from("https://api.github.com"))
+                    .trim_end_matches('/')
+                    .to_string(),
+                registry_repository: utils::env::registry_repository(),
+                registry_branch: utils::env::registry_branch(),
+                retries: utils::env::http_retries(),
+                backoff: utils::env::http_backoff(),
+                rate_limit_max_wait: utils::env::rate_limit_max_wait(),
+            }),
+            Err(err) => Err(Error::Client(err.to_string())),
         }
     }
 
-    pub fn get_file(
+    fn post<B: Serialize, R: DeserializeOwned>(
         &self,
-        repository: &String,
-        path: &String,
-        branch: &String,
-        output_format: &FileOutputFormat,
-    ) -> Result<String, bool> {
-        match self
-            .client
-            .get(format!(
-                "https://api.github.com/repos/{}/contents/{}?ref={}",
-                &repository, &path, &branch
-            ))
-            .header("Accept", "application/vnd.github.raw+json")
-            .send()
-        {
-            Ok(raw_response) => {
-                match raw_response.status() {
-                    StatusCode::OK => (),
-                    StatusCode::NOT_FOUND => {
-                        error!("The requested file was not found");
-                        return Err(false);
-                    }
-                    _ => {
-                        error!(
-                            "The response was undesired, status code: {}",
-                            &raw_response.status(),
-                        );
-                        return Err(true);
-                    }
-                }
-
-                match output_format {
-                    FileOutputFormat::UTF8 => match raw_response.text() {
-                        Ok(response) => Ok(response),
-                        Err(err) => {
-                            error!(
-                                "Something went wrong while deserializing the raw response to UTF-8: {}",
-                                &err
-                            );
-                            Err(true)
-                        }
-                    },
-                    FileOutputFormat::BASE64 => match raw_response.bytes() {
-                        Ok(response) => Ok(BASE64_STANDARD.encode(response)),
-                        Err(err) => {
-                            error!(
-                                "Something went wrong while serialzing the raw response to base64: {}",
-                                &err
-                            );
-                            Err(true)
-                        }
-                    },
-                }
-            }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(true)
-            }
-        }
+        url: &str,
+        body: &B,
+        expected_status: StatusCode,
+    ) -> Result<R, Error> {
+        let body_string = serde_json::to_string(body).map_err(|err| Error::Serialize {
+            path: url.to_string(),
+            message: err.to_string(),
+        })?;
+
+        Self::deserialize(
+            self.send(
+                self.client.post(url).body(body_string),
+                url,
+                expected_status,
+            )?,
+            url,
+        )
     }
 
-    pub fn get_directory(
+    /*
+     * Sends the request, retrying it after transient failures. Server and connection errors are
+     * retried with an exponential backoff, an exhausted rate limit is waited out until it resets.
+     */
+    fn send(
         &self,
-        repository: &String,
-        path: &String,
-        branch: &String,
-    ) -> Result<Vec<GetContentDirectoryEntryResponse>, ()> {
-        match self
-            .client
-            .get(format!(
-                "https://api.github.com/repos/{}/contents/{}?ref={}",
-                &repository, &path, &branch
-            ))
-            .send()
-        {
-            Ok(raw_response) => {
-                if raw_response.status() != StatusCode::OK {
-                    error!(
-                        "The response was undesired, status code: {}",
-                        &raw_response.status(),
-                    );
-                    return Err(());
+        request: RequestBuilder,
+        path: &str,
+        expected_status: StatusCode,
+    ) -> Result<Response, Error> {
+        let mut attempt = 0;
+        loop {
+            let attempt_request = request.try_clone().ok_or_else(|| Error::Request {
+                path: path.to_string(),
+                message: String::from("the request can not be retried"),
+            })?;
+
+            let (err, wait) = match attempt_request.send() {
+                Ok(raw_response) if raw_response.status() == expected_status => {
+                    return Ok(raw_response);
                 }
-
-                match raw_response.json::<Vec<GetContentDirectoryEntryResponse>>() {
-                    Ok(response) => Ok(response),
-                    Err(err) => {
-                        error!(
-                            "Something went wrong while deserializing the response to JSON: {}",
-                            &err
-                        );
-                        Err(())
+                Ok(raw_response) => {
+                    let wait = rate_limit_wait(raw_response.headers());
+                    match Error::from_response(path, raw_response) {
+                        err @ Error::RateLimited { .. } => {
+                            (err, wait.unwrap_or(DEFAULT_RATE_LIMIT_WAIT))
+                        }
+                        err => (err, self.backoff(attempt)),
                     }
                 }
+                Err(err) => (
+                    Error::Request {
+                        path: path.to_string(),
+                        message: err.to_string(),
+                    },
+                    self.backoff(attempt),
+                ),
+            };
+
+            if !err.is_transient()
+                || attempt >= self.retries
+                || (matches!(err, Error::RateLimited { .. }) && wait > self.rate_limit_max_wait)
+            {
+                return Err(err);
             }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(())
-            }
+
+            attempt += 1;
+            warn!(
+                "{}, retrying in {:.1}s ({}/{})",
+                err,
+                wait.as_secs_f64(),
+                attempt,
+                self.retries
+            );
+            thread::sleep(wait);
         }
     }
 
-    pub fn get_branch(
-        &self,
-        repository: &String,
-        branch: &String,
-    ) -> Result<GetBranchResponse, ()> {
-        match self
-            .client
-            .get(format!(
-                "https://api.github.com/repos/{}/branches/{}",
-                &repository, &branch
-            ))
-            .send()
-        {
-            Ok(raw_response) => {
-                if raw_response.status() != 200 {
-                    error!(
-                        "The response was undesired, status code: {}",
-                        &raw_response.status(),
-                    );
-                    return Err(());
-                }
+    /*
+     * The delay before the given retry, jittered so concurrent runs do not retry in lockstep.
+     */
+    fn backoff(&self, attempt: u32) -> Duration {
+        let delay = self
+            .backoff
+            .saturating_mul(2_u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF);
+        let jitter = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.subsec_nanos() % 1000);
+
+        delay.mul_f64(0.5 + f64::from(jitter) / 2000.0)
+    }
 
-                match raw_response.json::<GetBranchResponse>() {
-                    Ok(response) => Ok(response),
-                    Err(err) => {
-                        error!(
-                            "Something went wrong while deserializing the response to JSON: {}",
-                            &err
-                        );
-                        Err(())
-                    }
-                }
-            }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(())
-            }
-        }
+    fn deserialize<R: DeserializeOwned>(raw_response: Response, path: &str) -> Result<R, Error> {
+        raw_response.json::<R>().map_err(|err| Error::Deserialize {
+            path: path.to_string(),
+            message: err.to_string(),
+        })
     }
+}
 
-    pub fn create_blob(&self, content: String, encoding: String) -> Result<CreateBlobResponse, ()> {
-        let body = CreateBlobRequest { content, encoding };
+/*
+ * How long to wait for an exhausted rate limit, based on the Retry-After header or otherwise on
+ * the moment the rate limit resets.
+ */
+fn rate_limit_wait(headers: &HeaderMap) -> Option<Duration> {
+    let header = |name: &str| {
+        headers
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse::<u64>().ok())
+    };
+
+    if let Some(seconds) = header("Retry-After") {
+        return Some(Duration::from_secs(seconds));
+    }
 
-        let p_response = match serde_json::to_string(&body) {
-            Ok(body_string) => self
-                .client
-                .post("https://api.github.com/repos/paperback-community/extensions/git/blobs")
-                .body(body_string)
-                .send(),
-            Err(err) => {
-                error!(
-                    "Something went wrong while serializing the request body to JSON: {}",
-                    &err
-                );
-                return Err(());
-            }
-        };
+    if headers
+        .get("X-RateLimit-Remaining")
+        .is_some_and(|remaining| remaining == "0")
+    {
+        let reset = header("X-RateLimit-Reset")?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).ok()?.as_secs();
+        // The reset time only has a precision of seconds, so wait an extra second to be safe
+        return Some(Duration::from_secs(reset.saturating_sub(now) + 1));
+    }
 
-        match p_response {
-            Ok(raw_response) => {
-                if raw_response.status() != 201 {
-                    error!(
-                        "The response was undesired, status code: {}",
-                        &raw_response.status(),
-                    );
-                    return Err(());
-                }
+    None
+}
 
-                match raw_response.json::<CreateBlobResponse>() {
-                    Ok(response) => Ok(response),
-                    Err(err) => {
-                        error!(
-                            "Something went wrong while deserializing the response to JSON: {}",
-                            &err
-                        );
-                        Err(())
-                    }
-                }
-            }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(())
-            }
+impl Backend for Requests {
+    fn get_file(
+        &self,
+        repository: &str,
+        path: &str,
+        branch: &str,
+        output_format: &FileOutputFormat,
+    ) -> Result<String, Error> {
+        let file_path = format!("{}/{}@{}", &repository, &path, &branch);
+
+        let raw_response = self.send(
+            self.client
+                .get(format!(
+                    "{}/repos/{}/contents/{}?ref={}",
+                    &self.api_url, &repository, &path, &branch
+                ))
+                .header("Accept", "application/vnd.github.raw+json"),
+            &file_path,
+            StatusCode::OK,
+        )?;
+
+        match output_format {
+            FileOutputFormat::UTF8 => raw_response.text().map_err(|err| Error::Encoding {
+                path: file_path,
+                message: err.to_string(),
+            }),
+            FileOutputFormat::BASE64 => match raw_response.bytes() {
+                Ok(response) => Ok(BASE64_STANDARD.encode(response)),
+                Err(err) => Err(Error::Encoding {
+                    path: file_path,
+                    message: err.to_string(),
+                }),
+            },
         }
     }
 
-    pub fn create_tree(
+    fn get_tree(&self, repository: &str, reference: &str) -> Result<GetTreeResponse, Error> {
+        let tree_path = format!("{}@{}", &repository, &reference);
+
+        Self::deserialize(
+            self.send(
+                self.client.get(format!(
+                    "{}/repos/{}/git/trees/{}?recursive=1",
+                    &self.api_url, &repository, &reference
+                )),
+                &tree_path,
+                StatusCode::OK,
+            )?,
+            &tree_path,
+        )
+    }
+
+    fn get_branch(&self, repository: &str, branch: &str) -> Result<GetBranchResponse, Error> {
+        let branch_path = format!("{}@{}", &repository, &branch);
+
+        Self::deserialize(
+            self.send(
+                self.client.get(format!(
+                    "{}/repos/{}/branches/{}",
+                    &self.api_url, &repository, &branch
+                )),
+                &branch_path,
+                StatusCode::OK,
+            )?,
+            &branch_path,
+        )
+    }
+
+    fn create_blob(&self, content: String, encoding: String) -> Result<CreateBlobResponse, Error> {
+        let body = CreateBlobRequest { content, encoding };
+
+        self.post(
+            &format!(
+                "{}/repos/{}/git/blobs",
+                &self.api_url, &self.registry_repository
+            ),
+            &body,
+            StatusCode::CREATED,
+        )
+    }
+
+    fn create_tree(
         &self,
         base_tree: String,
         managed_extensions: ManagedExtensions,
-    ) -> Result<CreateTreeResponse, ()> {
+    ) -> Result<CreateTreeResponse, Error> {
         let mut tree = vec![];
         for managed_extension in managed_extensions {
             for managed_extension_file in managed_extension.2.keys() {
@@ -352,57 +410,24 @@ impl Requests {
 
         let body = CreateTreeRequest { base_tree, tree };
 
-        let p_response = match serde_json::to_string(&body) {
-            Ok(body_string) => self
-                .client
-                .post("https://api.github.com/repos/paperback-community/extensions/git/trees")
-                .body(body_string)
-                .send(),
-            Err(err) => {
-                error!(
-                    "Something went wrong while serializing the request body to JSON: {}",
-                    &err
-                );
-                return Err(());
-            }
-        };
-
-        match p_response {
-            Ok(raw_response) => {
-                if raw_response.status() != 201 {
-                    error!(
-                        "The response was undesired, status code: {}",
-                        &raw_response.status(),
-                    );
-                    return Err(());
-                }
-
-                match raw_response.json::<CreateTreeResponse>() {
-                    Ok(response) => Ok(response),
-                    Err(err) => {
-                        error!(
-                            "Something went wrong while deserializing the response to JSON: {}",
-                            &err
-                        );
-                        Err(())
-                    }
-                }
-            }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(())
-            }
-        }
+        self.post(
+            &format!(
+                "{}/repos/{}/git/trees",
+                &self.api_url, &self.registry_repository
+            ),
+            &body,
+            StatusCode::CREATED,
+        )
     }
 
-    pub fn create_commit(
+    fn create_commit(
         &self,
         message: String,
         tree_sha: String,
         parent_commit_sha: String,
         author_name: String,
         author_email: String,
-    ) -> Result<CreateCommitResponse, ()> {
+    ) -> Result<CreateCommitResponse, Error> {
         let body = CreateCommitRequest {
             message,
             tree: tree_sha,
@@ -413,86 +438,42 @@ impl Requests {
             },
         };
 
-        let p_response = match serde_json::to_string(&body) {
-            Ok(body_string) => self
-                .client
-                .post("https://api.github.com/repos/paperback-community/extensions/git/commits")
-                .body(body_string)
-                .send(),
-            Err(err) => {
-                error!(
-                    "Something went wrong while serializing the request body to JSON: {}",
-                    &err
-                );
-                return Err(());
-            }
-        };
-
-        match p_response {
-            Ok(raw_response) => {
-                if raw_response.status() != 201 {
-                    error!(
-                        "The response was undesired, status code: {}",
-                        &raw_response.status(),
-                    );
-                    return Err(());
-                }
-
-                match raw_response.json::<CreateCommitResponse>() {
-                    Ok(response) => Ok(response),
-                    Err(err) => {
-                        error!(
-                            "Something went wrong while deserializing the response to JSON: {}",
-                            &err
-                        );
-                        Err(())
-                    }
-                }
-            }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(())
-            }
-        }
+        self.post(
+            &format!(
+                "{}/repos/{}/git/commits",
+                &self.api_url, &self.registry_repository
+            ),
+            &body,
+            StatusCode::CREATED,
+        )
     }
 
-    pub fn update_reference(&self, commit_sha: String) -> Result<(), ()> {
-        let body = UpdateReferenceRequest { sha: commit_sha };
-
-        let p_body_string = serde_json::to_string(&body);
-
-        let p_response = match p_body_string {
-            Ok(body_string) => self
-                .client
-                .post(
-                    "https://api.github.com/repos/paperback-community/extensions/git/refs/heads/master",
-                )
-                .body(body_string)
-                .send(),
-            Err(err) => {
-                error!(
-                    "Something went wrong while serializing the request body to JSON: {}",
-                    &err
-                );
-                return Err(());
-            }
+    fn update_reference(&self, commit_sha: String) -> Result<(), Error> {
+        let url = &format!(
+            "{}/repos/{}/git/refs/heads/{}",
+            &self.api_url, &self.registry_repository, &self.registry_branch
+        );
+        let body = UpdateReferenceRequest {
+            sha: commit_sha,
+            force: false,
         };
 
-        match p_response {
-            Ok(raw_response) => {
-                if raw_response.status() != 200 {
-                    error!(
-                        "The response was undesired, status code: {}",
-                        &raw_response.status(),
-                    );
-                    return Err(());
-                }
-                Ok(())
-            }
-            Err(err) => {
-                error!("Something went wrong while making the request: {}", &err);
-                Err(())
+        let body_string = serde_json::to_string(&body).map_err(|err| Error::Serialize {
+            path: url.to_string(),
+            message: err.to_string(),
+        })?;
+
+        match self.send(
+            self.client.patch(url).body(body_string),
+            url,
+            StatusCode::OK,
+        ) {
+            Ok(_) => Ok(()),
+            // GitHub rejects reference updates which are not a fast forward with this status
+            Err(Error::Status { path, status }) if status == StatusCode::UNPROCESSABLE_ENTITY => {
+                Err(Error::NotFastForward { path })
             }
+            Err(err) => Err(err),
         }
     }
 }
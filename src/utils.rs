@@ -1,2 +1,4 @@
 pub mod env;
+pub mod git;
 pub mod logger;
+pub mod parallel;
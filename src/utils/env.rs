@@ -1,24 +1,22 @@
-use std::env;
-
-use tracing::error;
+use std::{env, time::Duration};
 
 #[cfg(feature = "dotenv")]
 use dotenvy;
 
+use crate::error::Error;
+
 #[cfg(feature = "dotenv")]
-pub fn load_dotenv() -> Result<(), ()> {
+pub fn load_dotenv() -> Result<(), Error> {
     if let Err(err) = dotenvy::dotenv() {
-        eprintln!(
-            "An error occurred wile trying to load the .env file: {}",
-            &err
-        );
-        return Err(());
+        let err = Error::Dotenv(err.to_string());
+        eprintln!("{}", &err);
+        return Err(err);
     }
 
     Ok(())
 }
 
-pub fn validate() -> Result<(), ()> {
+pub fn validate() -> Result<(), Error> {
     /*
      * Excluded because ${{ secrets.GITHUB_TOKEN }} will be used by default instead:
      *match env::var("GITHUB_TOKEN") {
@@ -35,31 +33,289 @@ pub fn validate() -> Result<(), ()> {
      *};
      */
 
+    if let Ok(value) = env::var("REGISTRY_REPOSITORY")
+        && value.split('/').filter(|part| !part.is_empty()).count() != 2
+    {
+        return Err(Error::Environment {
+            variable: String::from("REGISTRY_REPOSITORY"),
+            message: String::from("it should be of the structure \"<owner>/<repository_name>\""),
+        });
+    }
+
+    match env::var("BACKEND").as_deref() {
+        Err(_) | Ok("github") => (),
+        Ok("local") => {
+            if env::var("LOCAL_PATH").is_err() {
+                return Err(Error::Environment {
+                    variable: String::from("LOCAL_PATH"),
+                    message: String::from("it must be set when using the local backend"),
+                });
+            }
+        }
+        Ok(_) => {
+            return Err(Error::Environment {
+                variable: String::from("BACKEND"),
+                message: String::from("it should be either \"github\" or \"local\""),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Validates the environment variable which describes the extension repository, it is only
+ * required when syncing a repository to the registry.
+ */
+pub fn validate_repository() -> Result<(), Error> {
     if let Ok(value) = env::var("REPOSITORY") {
-        if !value.to_string().starts_with("paperback-community/") || value.to_string().len() < 20 {
-            error!(
-                "The provided repository is invalid, it should be of the structure \"paperback-community/<repository_name>\", consider using \"$${{ github.repository_name }}\""
-            );
-            return Err(());
+        if !value.starts_with(&(owner() + "/")) || value.len() <= owner().len() + 1 {
+            return Err(Error::Environment {
+                variable: String::from("REPOSITORY"),
+                message: format!(
+                    "it should be of the structure \"{}/<repository_name>\", consider using \"$${{{{ github.repository_name }}}}\"",
+                    owner()
+                ),
+            });
         }
     } else {
-        error!("The REPOSITORY environment variable was not set");
-        return Err(());
+        return Err(Error::Environment {
+            variable: String::from("REPOSITORY"),
+            message: String::from("it was not set"),
+        });
+    }
+
+    if let Ok(value) = env::var("CHANGED_CONTENT")
+        && !["warn", "fail", "update"].contains(&value.as_str())
+    {
+        return Err(Error::Environment {
+            variable: String::from("CHANGED_CONTENT"),
+            message: String::from("it should be either \"warn\", \"fail\" or \"update\""),
+        });
     }
 
+    Ok(())
+}
+
+pub fn validate_branch() -> Result<(), Error> {
     if let Ok(value) = env::var("BRANCH") {
         if (!value.to_string().ends_with("/stable") && !value.to_string().ends_with("/testing"))
             || value.to_string().len() < 7
         {
-            error!(
-                "The provided branch is invalid, it should be of the structure \"<paperback_major_minor_semver/<stable/testing>>\", consider using \"$${{ github.ref_name }}\""
-            );
-            return Err(());
+            return Err(Error::Environment {
+                variable: String::from("BRANCH"),
+                message: String::from(
+                    "it should be of the structure \"<paperback_major_minor_semver/<stable/testing>>\", consider using \"$${{ github.ref_name }}\"",
+                ),
+            });
         }
     } else {
-        error!("The BRANCH environment variable was not set");
-        return Err(());
+        return Err(Error::Environment {
+            variable: String::from("BRANCH"),
+            message: String::from("it was not set"),
+        });
     }
 
     Ok(())
 }
+
+/*
+ * Extensions are promoted to a stable branch from its testing branch, so the branch has to be a
+ * stable one.
+ */
+pub fn validate_stable_branch() -> Result<(), Error> {
+    validate_branch()?;
+
+    if testing_branch().is_none() {
+        return Err(Error::Environment {
+            variable: String::from("BRANCH"),
+            message: String::from(
+                "it should be a stable branch, extensions are promoted to it from the matching testing branch",
+            ),
+        });
+    }
+
+    Ok(())
+}
+
+pub fn registry_repository() -> String {
+    env::var("REGISTRY_REPOSITORY")
+        .unwrap_or_else(|_| String::from("paperback-community/extensions"))
+}
+
+pub fn registry_branch() -> String {
+    env::var("REGISTRY_BRANCH").unwrap_or_else(|_| String::from("master"))
+}
+
+pub fn repository_branch() -> String {
+    env::var("REPOSITORY_BRANCH").unwrap_or_else(|_| String::from("gh-pages"))
+}
+
+pub fn owner() -> String {
+    env::var("REPOSITORY_OWNER").unwrap_or_else(|_| String::from("paperback-community"))
+}
+
+/*
+ * The name of the extension repository without its owner, which is used as its key in the
+ * metadata file.
+ */
+pub fn repository_name() -> String {
+    env::var("REPOSITORY").unwrap()[owner().len() + 1..].to_string()
+}
+
+/*
+ * The testing branch matching the stable branch which is being managed, None when a testing branch
+ * is being managed.
+ */
+pub fn testing_branch() -> Option<String> {
+    env::var("BRANCH")
+        .ok()?
+        .strip_suffix("/stable")
+        .map(|version| version.to_string() + "/testing")
+}
+
+/*
+ * The labels of the badges which may only be used by extensions on testing branches, compared
+ * case insensitively.
+ */
+pub fn testing_badges() -> Vec<String> {
+    env::var("TESTING_BADGES")
+        .unwrap_or_else(|_| String::from("Beta, Experimental"))
+        .split(',')
+        .map(|label| label.trim().to_lowercase())
+        .filter(|label| !label.is_empty())
+        .collect()
+}
+
+/*
+ * The ids of the extensions which are promoted, separated by commas, without duplicates.
+ */
+pub fn extension_ids() -> Vec<String> {
+    let mut ids = vec![];
+    for id in env::var("EXTENSION_ID").unwrap_or_default().split(',') {
+        let id = id.trim().to_string();
+        if !id.is_empty() && !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    ids
+}
+
+/*
+ * The ids of the extensions which may be replaced by a lower version from the repository.
+ */
+pub fn rollbacks() -> Vec<String> {
+    env::var("ROLLBACK")
+        .unwrap_or_default()
+        .split(',')
+        .map(|id| id.trim().to_string())
+        .filter(|id| !id.is_empty())
+        .collect()
+}
+
+/*
+ * The names of the extension repositories, without their owner, which extensions can be published
+ * from. Used when rebuilding the metadata file.
+ */
+pub fn repositories() -> Vec<String> {
+    env::var("REPOSITORIES")
+        .unwrap_or_default()
+        .split(',')
+        .map(|repository| {
+            let repository = repository.trim();
+            repository
+                .strip_prefix(&(owner() + "/"))
+                .unwrap_or(repository)
+                .to_string()
+        })
+        .filter(|repository| !repository.is_empty())
+        .collect()
+}
+
+/*
+ * The amount of times a sync gets retried when the registry moved while it was being updated.
+ */
+pub fn commit_retries() -> u32 {
+    env::var("COMMIT_RETRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(3)
+}
+
+/*
+ * The amount of times a request to the GitHub API gets retried after a transient failure, such as
+ * a server error, a connection error or an exhausted rate limit.
+ */
+pub fn http_retries() -> u32 {
+    env::var("HTTP_RETRIES")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(5)
+}
+
+pub fn http_timeout() -> Duration {
+    Duration::from_secs(
+        env::var("HTTP_TIMEOUT")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(15),
+    )
+}
+
+/*
+ * The delay before the first retry of a request, which doubles with every following retry.
+ */
+pub fn http_backoff() -> Duration {
+    Duration::from_millis(
+        env::var("HTTP_BACKOFF")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(1000),
+    )
+}
+
+/*
+ * The longest the tool waits for an exhausted rate limit to reset before giving up.
+ */
+pub fn rate_limit_max_wait() -> Duration {
+    Duration::from_secs(
+        env::var("RATE_LIMIT_MAX_WAIT")
+            .ok()
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(900),
+    )
+}
+
+/*
+ * The amount of extension files which get fetched and turned into blobs at the same time.
+ */
+pub fn concurrency() -> usize {
+    env::var("CONCURRENCY")
+        .ok()
+        .and_then(|value| value.parse().ok())
+        .filter(|concurrency| *concurrency > 0)
+        .unwrap_or(8)
+}
+
+/*
+ * What happens to extensions whose content changed without a version bump.
+ */
+#[derive(PartialEq)]
+pub enum ChangedContent {
+    Warn,
+    Fail,
+    Update,
+}
+
+pub fn changed_content() -> ChangedContent {
+    match env::var("CHANGED_CONTENT").as_deref() {
+        Ok("fail") => ChangedContent::Fail,
+        Ok("update") => ChangedContent::Update,
+        _ => ChangedContent::Warn,
+    }
+}
+
+pub fn dry_run() -> bool {
+    env::var("DRY_RUN").is_ok_and(|value| value == "true" || value == "1")
+}
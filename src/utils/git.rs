@@ -0,0 +1,108 @@
+use std::{collections::BTreeMap, fmt::Write, fs, io, path::Path};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use sha1::{Digest, Sha1};
+
+use crate::error::Error;
+
+pub fn decode(content: &str, encoding: &str) -> Result<Vec<u8>, Error> {
+    if encoding != "base64" {
+        return Ok(content.as_bytes().to_vec());
+    }
+
+    BASE64_STANDARD
+        .decode(content)
+        .map_err(|err| Error::Encoding {
+            path: String::from("blob"),
+            message: err.to_string(),
+        })
+}
+
+/*
+ * Selects the files of a tree listing, mapped from their path to their blob SHA, which are below
+ * the given directory.
+ */
+pub fn directory_files<'f>(
+    files: &'f BTreeMap<String, String>,
+    directory: &str,
+) -> BTreeMap<&'f String, &'f String> {
+    let prefix = format!("{directory}/");
+
+    files
+        .range(prefix.clone()..)
+        .take_while(|(path, _)| path.starts_with(&prefix))
+        .collect()
+}
+
+/*
+ * Computes the SHA Git would assign to a blob with the given content, this matches the SHA the
+ * GitHub API returns when creating the same blob.
+ */
+pub fn blob_sha(content: &[u8]) -> String {
+    to_hex(&hash_object("blob", content))
+}
+
+/*
+ * Computes the SHA Git would assign to the tree of the given directory, empty directories are
+ * ignored the same way Git ignores them.
+ */
+pub fn tree_sha(directory: &Path) -> io::Result<Option<String>> {
+    Ok(tree_hash(directory)?.map(|hash| to_hex(&hash)))
+}
+
+fn tree_hash(directory: &Path) -> io::Result<Option<Vec<u8>>> {
+    let mut entries = vec![];
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        if name == ".git" {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            if let Some(hash) = tree_hash(&entry.path())? {
+                entries.push((name + "/", "40000", hash));
+            }
+        } else {
+            entries.push((
+                name,
+                "100644",
+                hash_object("blob", &fs::read(entry.path())?),
+            ));
+        }
+    }
+
+    if entries.is_empty() {
+        return Ok(None);
+    }
+
+    // Git sorts tree entries as if directory names had a trailing slash
+    entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+    let mut content = vec![];
+    for (name, mode, hash) in entries {
+        content.extend_from_slice(mode.as_bytes());
+        content.push(b' ');
+        content.extend_from_slice(name.trim_end_matches('/').as_bytes());
+        content.push(0);
+        content.extend_from_slice(&hash);
+    }
+
+    Ok(Some(hash_object("tree", &content)))
+}
+
+fn hash_object(kind: &str, content: &[u8]) -> Vec<u8> {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("{kind} {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    hasher.finalize().to_vec()
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .fold(String::with_capacity(40), |mut hex, byte| {
+            let _ = write!(hex, "{byte:02x}");
+            hex
+        })
+}
@@ -1,7 +1,9 @@
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::{EnvFilter, Registry, fmt, prelude::*};
 
-pub fn new() -> Result<(), ()> {
+use crate::error::Error;
+
+pub fn new() -> Result<(), Error> {
     let env_filter = EnvFilter::builder()
         .with_default_directive(LevelFilter::INFO.into())
         .with_env_var("LOG_LEVEL")
@@ -10,17 +12,15 @@ pub fn new() -> Result<(), ()> {
     let subscriber = Registry::default()
         .with(
             fmt::Layer::default()
-                .with_writer(std::io::stdout)
+                .with_writer(std::io::stderr)
                 .with_filter(env_filter),
         )
         .try_init();
 
     if let Err(err) = subscriber {
-        eprintln!(
-            "Something went wrong while initializing the logger: {}",
-            &err
-        );
-        return Err(());
+        let err = Error::Logger(err.to_string());
+        eprintln!("{}", &err);
+        return Err(err);
     }
 
     Ok(())
@@ -0,0 +1,55 @@
+use std::{
+    sync::{
+        Mutex,
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
+    thread,
+};
+
+use crate::error::Error;
+
+/*
+ * Applies the function to every item using at most the given amount of threads, returning the
+ * results in the order of the items. Once an item fails no new items are started and the error of
+ * the first failed item is returned.
+ */
+pub fn map<T, R, F>(items: &[T], threads: usize, function: F) -> Result<Vec<R>, Error>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> Result<R, Error> + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let results = Mutex::new(
+        (0..items.len())
+            .map(|_| None)
+            .collect::<Vec<Option<Result<R, Error>>>>(),
+    );
+
+    thread::scope(|scope| {
+        for _ in 0..threads.clamp(1, items.len().max(1)) {
+            scope.spawn(|| {
+                while !failed.load(Ordering::Relaxed) {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(item) = items.get(index) else {
+                        break;
+                    };
+
+                    let result = function(item);
+                    if result.is_err() {
+                        failed.store(true, Ordering::Relaxed);
+                    }
+                    results.lock().unwrap()[index] = Some(result);
+                }
+            });
+        }
+    });
+
+    let results = results.into_inner().unwrap();
+    if failed.into_inner() {
+        return Err(results.into_iter().flatten().find_map(Result::err).unwrap());
+    }
+
+    Ok(results.into_iter().flatten().flatten().collect())
+}
@@ -0,0 +1,243 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+use crate::{
+    backend::Backend,
+    error::Error,
+    requests::FileOutputFormat,
+    utils,
+    versioning::{JsonFileAsStruct, Metadata, Versioning},
+};
+
+/*
+ * A single inconsistency in the registry, reported as JSON so it can be processed by other tools.
+ */
+#[derive(Debug, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Drift {
+    InvalidFile {
+        path: String,
+        message: String,
+    },
+    MissingMetadata {
+        branch: String,
+    },
+    MissingOwner {
+        branch: String,
+        id: String,
+    },
+    MultipleOwners {
+        branch: String,
+        id: String,
+        repositories: Vec<String>,
+    },
+    MissingSource {
+        branch: String,
+        id: String,
+        repository: String,
+    },
+    MissingIndex {
+        branch: String,
+        id: String,
+    },
+    OrphanedDirectory {
+        branch: String,
+        directory: String,
+    },
+}
+
+struct RegistryTree {
+    files: BTreeSet<String>,
+    directories: BTreeSet<String>,
+}
+
+/*
+ * The branch a versioning file belongs to, only <version>/<stable|testing>/versioning.json paths
+ * are versioning files of a branch, files of extensions can have the same name.
+ */
+fn branch_of(path: &str) -> Option<&str> {
+    let branch = path.strip_suffix("/versioning.json")?;
+
+    match branch.split('/').collect::<Vec<&str>>()[..] {
+        [_, "stable" | "testing"] => Some(branch),
+        _ => None,
+    }
+}
+
+/*
+ * Cross-checks the versioning and metadata files of every branch in the registry against each
+ * other and against the files in the registry, printing every inconsistency which was found.
+ */
+pub fn run(backend: &dyn Backend) -> Result<(), Error> {
+    let registry_repository = utils::env::registry_repository();
+
+    info!("Fetching the latest commit in the registry");
+    let registry_commit = backend
+        .get_branch(&registry_repository, &utils::env::registry_branch())?
+        .commit
+        .sha;
+
+    info!("Listing the files in the registry");
+    let response = backend.get_tree(&registry_repository, &registry_commit)?;
+    if response.truncated {
+        return Err(Error::TruncatedTree {
+            path: format!("{registry_repository}@{registry_commit}"),
+        });
+    }
+
+    let mut tree = RegistryTree {
+        files: BTreeSet::new(),
+        directories: BTreeSet::new(),
+    };
+    for entry in response.tree {
+        match entry.etype.as_str() {
+            "blob" => tree.files.insert(entry.path),
+            "tree" => tree.directories.insert(entry.path),
+            _ => false,
+        };
+    }
+
+    let mut drift = vec![];
+    for branch in tree.files.iter().filter_map(|path| branch_of(path)) {
+        info!("Verifying the {} branch", branch);
+        verify_branch(
+            backend,
+            (&registry_repository, &registry_commit),
+            branch,
+            &tree,
+            &mut drift,
+        )?;
+    }
+
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&drift).map_err(|err| Error::Serialize {
+            path: String::from("drift"),
+            message: err.to_string(),
+        })?
+    );
+
+    if !drift.is_empty() {
+        return Err(Error::Drift { count: drift.len() });
+    }
+
+    info!("The registry is consistent");
+    Ok(())
+}
+
+fn verify_branch(
+    backend: &dyn Backend,
+    registry: (&str, &str),
+    branch: &str,
+    tree: &RegistryTree,
+    drift: &mut Vec<Drift>,
+) -> Result<(), Error> {
+    let Some(versioning) = request_file::<Versioning>(backend, registry, branch, drift)? else {
+        return Ok(());
+    };
+
+    if !tree
+        .files
+        .contains(&format!("{branch}/{}", Metadata::FILE_NAME))
+    {
+        warn!("The {} branch has no metadata file", branch);
+        drift.push(Drift::MissingMetadata {
+            branch: branch.to_string(),
+        });
+        return Ok(());
+    }
+
+    let Some(metadata) = request_file::<Metadata>(backend, registry, branch, drift)? else {
+        return Ok(());
+    };
+
+    let source_ids = versioning.source_ids().collect::<BTreeSet<&String>>();
+
+    let mut owners = BTreeMap::<&String, Vec<String>>::new();
+    for (repository, id) in metadata.extensions() {
+        owners.entry(id).or_default().push(repository.clone());
+
+        if !source_ids.contains(id) {
+            drift.push(Drift::MissingSource {
+                branch: branch.to_string(),
+                id: id.clone(),
+                repository: repository.clone(),
+            });
+        }
+    }
+
+    for id in &source_ids {
+        match owners.get(id).map(Vec::len) {
+            None => drift.push(Drift::MissingOwner {
+                branch: branch.to_string(),
+                id: (*id).clone(),
+            }),
+            Some(1) => (),
+            Some(_) => drift.push(Drift::MultipleOwners {
+                branch: branch.to_string(),
+                id: (*id).clone(),
+                repositories: owners[id].clone(),
+            }),
+        }
+    }
+
+    for id in owners.keys() {
+        if !tree.files.contains(&format!("{branch}/{id}/index.js")) {
+            drift.push(Drift::MissingIndex {
+                branch: branch.to_string(),
+                id: (*id).clone(),
+            });
+        }
+    }
+
+    let prefix = format!("{branch}/");
+    for directory in &tree.directories {
+        if let Some(name) = directory.strip_prefix(&prefix)
+            && !name.contains('/')
+            && !source_ids.contains(&name.to_string())
+        {
+            drift.push(Drift::OrphanedDirectory {
+                branch: branch.to_string(),
+                directory: directory.clone(),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/*
+ * Requests one of the JSON files of the branch, a file which can't be parsed is reported instead
+ * of failing the whole verification.
+ */
+fn request_file<JFAS>(
+    backend: &dyn Backend,
+    (registry_repository, registry_commit): (&str, &str),
+    branch: &str,
+    drift: &mut Vec<Drift>,
+) -> Result<Option<Box<JFAS>>, Error>
+where
+    JFAS: JsonFileAsStruct + for<'de> Deserialize<'de>,
+{
+    let path = format!("{branch}/{}", JFAS::FILE_NAME);
+    let response = backend.get_file(
+        registry_repository,
+        &path,
+        registry_commit,
+        &FileOutputFormat::UTF8,
+    )?;
+
+    match JFAS::new(&response) {
+        Ok(file) => Ok(Some(file)),
+        Err(err) => {
+            warn!("{}", err);
+            drift.push(Drift::InvalidFile {
+                path,
+                message: err.to_string(),
+            });
+            Ok(None)
+        }
+    }
+}
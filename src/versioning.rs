@@ -3,10 +3,16 @@ use std::{
     env,
 };
 
+use bitflags::bitflags;
 use chrono::Utc;
-use node_semver::Version;
+use node_semver::{Range, Version};
 use serde::{Deserialize, Deserializer, Serialize, Serializer, ser::SerializeSeq};
-use tracing::{error, warn};
+use tracing::{error, info, warn};
+
+use crate::{
+    error::Error,
+    utils::{self, env::ChangedContent},
+};
 
 #[derive(Debug, Default, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -43,8 +49,8 @@ pub struct Source {
     description: String,
     version: String,
     icon: String,
-    language: Option<String>,
-    content_rating: String,
+    language: Option<Language>,
+    content_rating: ContentRating,
     badges: Vec<Option<Badges>>,
     capabilities: Option<Capabilities>,
     developers: Vec<Option<Developers>>,
@@ -58,11 +64,45 @@ pub struct Badges {
     background_color: String,
 }
 
+/*
+ * The content rating of the Paperback types package, unknown ratings are kept as they are so they
+ * can be reported.
+ */
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ContentRating {
+    Everyone,
+    Mature,
+    Adult,
+    Other(String),
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Language(String);
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 enum Capabilities {
-    List(Vec<u8>),
-    Primtitive(u8),
+    List(Vec<SourceIntents>),
+    Primitive(SourceIntents),
+}
+
+bitflags! {
+    /*
+     * The source intents of the Paperback types package, unknown intents are retained so the
+     * capabilities are written back exactly as they were read.
+     */
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct SourceIntents: u32 {
+        const MANGA_CHAPTERS = 1 << 0;
+        const MANGA_TRACKING = 1 << 1;
+        const DISCOVER_SECTIONS = 1 << 2;
+        const COLLECTION_MANAGEMENT = 1 << 3;
+        const CLOUDFLARE_BYPASS_REQUIRED = 1 << 4;
+        const SETTINGS_UI = 1 << 5;
+        const MANGA_SEARCH = 1 << 6;
+    }
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -91,45 +131,100 @@ pub struct MetadataExtension {
     built_with: BuiltWith,
 }
 
+/*
+ * Extensions which may be moved from one repository to another, an entry gets removed once its
+ * extension has been transferred.
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Transfers(Vec<Transfer>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Transfer {
+    id: String,
+    from: String,
+    to: String,
+}
+
+/*
+ * Extensions which were taken down by the registry moderators, they won't be added to the registry
+ * again until their entry is removed.
+ */
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Takedowns(Vec<Takedown>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Takedown {
+    id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    reason: Option<String>,
+    time: String,
+}
+
+/*
+ * Rules for extensions which may not be added or updated in the registry, the rules of the
+ * blocklist file follow the default ones. Every field of a rule has to match for it to apply, a
+ * rule with intents matches sources which have all of them.
+ */
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(from = "Vec<BlockRule>")]
+pub struct Blocklist(Vec<BlockRule>);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlockRule {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    id_suffix: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    repository: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    versions: Option<Range>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content_rating: Option<ContentRating>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    intents: Option<SourceIntents>,
+    reason: String,
+}
+
 pub type ManagedExtensions = Vec<(String, ManageTypes, HashMap<String, Option<String>>)>;
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum ManageTypes {
     Addition,
     Update,
+    Rollback,
+    Transfer,
     Deletion,
 }
 
 pub trait JsonFileAsStruct {
-    fn new<'s>(response: &'s str) -> Result<Box<Self>, ()>
+    const FILE_NAME: &'static str;
+
+    fn new<'s>(response: &'s str) -> Result<Box<Self>, Error>
     where
         Self: Deserialize<'s>,
     {
         match serde_json::from_str(response) {
             Ok(file) => Ok(Box::new(file)),
-            Err(err) => {
-                error!(
-                    "An error occurred while deserializing the response JSON to a struct: {}",
-                    &err
-                );
-                Err(())
-            }
+            Err(err) => Err(Error::Deserialize {
+                path: Self::FILE_NAME.to_string(),
+                message: err.to_string(),
+            }),
         }
     }
 
-    fn to_utf8(&self) -> Result<String, ()>
+    fn to_utf8(&self) -> Result<String, Error>
     where
         Self: Serialize,
     {
         match serde_json::to_string_pretty(&self) {
             Ok(string) => Ok(string),
-            Err(err) => {
-                error!(
-                    "An error occurred while serializing the struct into UTF-8 JSON: {}",
-                    &err
-                );
-                Err(())
-            }
+            Err(err) => Err(Error::Serialize {
+                path: Self::FILE_NAME.to_string(),
+                message: err.to_string(),
+            }),
         }
     }
 }
@@ -158,30 +253,39 @@ where
         .collect())
 }
 
-impl JsonFileAsStruct for Versioning {}
+impl JsonFileAsStruct for Versioning {
+    const FILE_NAME: &'static str = "versioning.json";
+}
 
 impl Versioning {
     pub fn update(
         &mut self,
         metadata: &mut Metadata,
+        transfers: &mut Transfers,
+        (takedowns, blocklist): (&Takedowns, &Blocklist),
         repository_versioning: &Versioning,
-    ) -> Result<ManagedExtensions, ()> {
+        testing_versioning: Option<&Versioning>,
+        (repository_files, registry_files): (&BTreeMap<String, String>, &BTreeMap<String, String>),
+    ) -> Result<ManagedExtensions, Error> {
+        let Ok(repository_types_version) =
+            repository_versioning.built_with.types.parse::<Version>()
+        else {
+            return Err(Error::InvalidTypesVersion {
+                version: repository_versioning.built_with.types.clone(),
+            });
+        };
+
         if self
             .built_with
             .types
             .parse::<Version>()
             .unwrap_or_else(|_| Version::parse("0.9.0").unwrap())
-            > repository_versioning
-                .built_with
-                .types
-                .parse::<Version>()
-                .unwrap()
+            > repository_types_version
         {
-            error!(
-                "The repository was build with a @paperback/types version {} which was too low, expected version {} or higher",
-                repository_versioning.built_with.types, self.built_with.types
-            );
-            return Err(());
+            return Err(Error::TypesVersion {
+                registry: self.built_with.types.clone(),
+                repository: repository_versioning.built_with.types.clone(),
+            });
         }
 
         let mut managed_extensions = vec![];
@@ -196,12 +300,7 @@ impl Versioning {
 
         let mut registry_extensions = metadata
             .repositories
-            .get(
-                env::var("REPOSITORY")
-                    .unwrap()
-                    .strip_prefix("paperback-community/")
-                    .unwrap(),
-            )
+            .get(&utils::env::repository_name())
             .cloned()
             .unwrap_or_default()
             .extensions
@@ -223,20 +322,49 @@ impl Versioning {
 
         metadata
             .repositories
-            .entry(
-                env::var("REPOSITORY")
-                    .unwrap()
-                    .strip_prefix("paperback-community/")
-                    .unwrap()
-                    .to_string(),
-            )
+            .entry(utils::env::repository_name())
             .or_insert_with(|| MetadataRepository {
                 extensions: BTreeMap::new(),
             });
 
+        repository_extensions.retain(|extension| {
+            if takedowns.contains(extension) {
+                warn!(
+                    "The {} extension was taken down from the registry, ignoring it",
+                    extension
+                );
+                return false;
+            }
+
+            let source = repository_versioning.sources.get(extension).unwrap();
+            if !admissible(
+                source,
+                blocklist,
+                &utils::env::repository_name(),
+                repository_files,
+            ) {
+                return false;
+            }
+
+            // Extensions only reach a stable branch after they were published to its testing branch
+            if let Some(testing_versioning) = testing_versioning
+                && !self.sources.contains_key(extension)
+                && !testing_versioning.tested(source)
+            {
+                warn!(
+                    "The {} extension has to be published to the testing branch first, with the same or a higher version, ignoring it",
+                    extension
+                );
+                return false;
+            }
+
+            true
+        });
+
         self.extension_additions(
             repository_versioning,
             metadata,
+            transfers,
             &repository_extensions,
             &mut managed_extensions,
         );
@@ -244,9 +372,11 @@ impl Versioning {
         self.extension_updates(
             repository_versioning,
             metadata,
+            blocklist,
+            (repository_files, registry_files),
             &shared_extensions,
             &mut managed_extensions,
-        );
+        )?;
 
         self.extension_deletions(metadata, &registry_extensions, &mut managed_extensions);
 
@@ -255,13 +385,108 @@ impl Versioning {
             return Ok(managed_extensions);
         }
 
+        self.stamp(&repository_versioning.built_with);
+
+        Ok(managed_extensions)
+    }
+
+    /*
+     * Copies the source of the extension from the testing branch into this stable branch, with
+     * the given files being the ones it would have on the stable branch. Returns None when the
+     * extension was already promoted.
+     */
+    pub fn promote(
+        &mut self,
+        metadata: &mut Metadata,
+        (testing_versioning, testing_metadata): (&Versioning, &Metadata),
+        (takedowns, blocklist): (&Takedowns, &Blocklist),
+        promoted_files: &BTreeMap<String, String>,
+        id: &str,
+    ) -> Result<Option<ManageTypes>, Error> {
+        let promotion_error = |reason: String| Error::Promotion {
+            id: id.to_string(),
+            reason,
+        };
+
+        let (Some(source), Some(owner)) = (
+            testing_versioning.sources.get(id),
+            testing_metadata.owner(id),
+        ) else {
+            return Err(promotion_error(String::from(
+                "it is not part of the testing branch",
+            )));
+        };
+
+        if takedowns.contains(id) {
+            return Err(promotion_error(String::from(
+                "it was taken down from the stable branch",
+            )));
+        }
+
+        if let Some(stable_owner) = metadata.owner(id)
+            && stable_owner != owner
+        {
+            return Err(promotion_error(format!(
+                "it belongs to the {stable_owner} repository on the stable branch"
+            )));
+        }
+
+        if !admissible(source, blocklist, owner, promoted_files) {
+            return Err(promotion_error(String::from(
+                "it is not admissible on the stable branch",
+            )));
+        }
+
+        let manage_type = match self.sources.get(id).map(|stable| {
+            (
+                source.version.parse::<Version>(),
+                stable.version.parse::<Version>(),
+            )
+        }) {
+            None => ManageTypes::Addition,
+            Some((Ok(version), Ok(stable_version))) if version == stable_version => {
+                info!("The {} extension was already promoted", id);
+                return Ok(None);
+            }
+            Some((Ok(version), Ok(stable_version))) if version < stable_version => {
+                return Err(promotion_error(format!(
+                    "its version {version} is lower than the stable version {stable_version}"
+                )));
+            }
+            Some(_) => ManageTypes::Update,
+        };
+
+        self.sources.insert(id.to_string(), source.clone());
+        metadata.insert(
+            owner,
+            id,
+            testing_metadata
+                .extension(owner, id)
+                .cloned()
+                .unwrap_or_else(|| MetadataExtension::new(testing_versioning)),
+        );
+        self.stamp(&testing_versioning.built_with);
+
+        Ok(Some(manage_type))
+    }
+
+    /*
+     * Marks the versioning file of the testing branch as rebuilt after extensions were promoted
+     * from it, keeping the toolchain it was built with.
+     */
+    pub fn restamp(&mut self) {
+        let built_with = self.built_with.clone();
+        self.stamp(&built_with);
+    }
+
+    /*
+     * Marks the versioning file as rebuilt with the given toolchain, naming the registry when the
+     * branch is new.
+     */
+    fn stamp(&mut self, built_with: &BuiltWith) {
         self.build_time = Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string();
-        self.built_with
-            .toolchain
-            .clone_from(&repository_versioning.built_with.toolchain);
-        self.built_with
-            .types
-            .clone_from(&repository_versioning.built_with.types);
+        self.built_with.toolchain.clone_from(&built_with.toolchain);
+        self.built_with.types.clone_from(&built_with.types);
 
         if self.repository.name.is_empty() {
             /*
@@ -273,44 +498,66 @@ impl Versioning {
                 "All extensions from the Paperback Community combined into a single repository.",
             );
         }
+    }
 
-        Ok(managed_extensions)
+    /*
+     * Whether the source was published to this testing branch, with the same or a higher version.
+     */
+    fn tested(&self, source: &Source) -> bool {
+        self.sources.get(&source.id).is_some_and(|tested| {
+            match (
+                source.version.parse::<Version>(),
+                tested.version.parse::<Version>(),
+            ) {
+                (Ok(version), Ok(tested_version)) => version <= tested_version,
+                _ => false,
+            }
+        })
+    }
+
+    pub fn source_ids(&self) -> impl Iterator<Item = &String> {
+        self.sources.keys()
+    }
+
+    /*
+     * Removes the extension from the sources, returning whether it was part of them.
+     */
+    pub fn remove_source(&mut self, id: &str) -> bool {
+        self.sources.remove(id).is_some()
     }
 
     fn extension_additions(
         &mut self,
         repository_versioning: &Versioning,
         metadata: &mut Metadata,
+        transfers: &mut Transfers,
         repository_extensions: &Vec<String>,
         managed_extensions: &mut ManagedExtensions,
     ) {
         for extension in repository_extensions {
-            if extension.ends_with("Template") {
-                warn!("Detected a template extension, ignoring it");
-                continue;
-            }
+            let mut manage_type = ManageTypes::Addition;
 
             if self.sources.contains_key(extension) {
-                warn!(
-                    "The {} extension already exists in another repository, it must be removed from there before it can be added to a new one, ignoring it",
-                    extension
-                );
-                continue;
-            }
+                let owner = metadata.owner(extension).cloned();
 
-            if repository_versioning
-                .sources
-                .get(extension)
-                .unwrap()
-                .version
-                .parse::<Version>()
-                .is_err()
-            {
-                error!(
-                    "The {} extension its version is not valid semver, ignoring it",
-                    extension
-                );
-                continue;
+                if let Some(owner) = owner
+                    && transfers.take(extension, &owner, &utils::env::repository_name())
+                {
+                    info!(
+                        "The {} extension is transferred from the {} repository",
+                        extension, owner
+                    );
+
+                    metadata.remove(&owner, extension);
+
+                    manage_type = ManageTypes::Transfer;
+                } else {
+                    warn!(
+                        "The {} extension already exists in another repository, it must be removed from there or transferred before it can be added to a new one, ignoring it",
+                        extension
+                    );
+                    continue;
+                }
             }
 
             self.sources.insert(
@@ -324,12 +571,7 @@ impl Versioning {
 
             metadata
                 .repositories
-                .get_mut(
-                    env::var("REPOSITORY")
-                        .unwrap()
-                        .strip_prefix("paperback-community/")
-                        .unwrap(),
-                )
+                .get_mut(&utils::env::repository_name())
                 .unwrap()
                 .extensions
                 .insert(
@@ -340,7 +582,7 @@ impl Versioning {
                     },
                 );
 
-            managed_extensions.push((extension.clone(), ManageTypes::Addition, HashMap::new()));
+            managed_extensions.push((extension.clone(), manage_type, HashMap::new()));
         }
     }
 
@@ -348,57 +590,120 @@ impl Versioning {
         &mut self,
         repository_versioning: &Versioning,
         metadata: &mut Metadata,
+        blocklist: &Blocklist,
+        (repository_files, registry_files): (&BTreeMap<String, String>, &BTreeMap<String, String>),
         shared_extensions: &Vec<String>,
         managed_extensions: &mut ManagedExtensions,
-    ) {
+    ) -> Result<(), Error> {
+        let rollbacks = utils::env::rollbacks();
+        let changed_content = utils::env::changed_content();
+        let mut changed_extensions = vec![];
+
         for extension in shared_extensions {
-            if repository_versioning
+            let Ok(repository_version) = repository_versioning
                 .sources
                 .get(extension)
                 .unwrap()
                 .version
                 .parse::<Version>()
-                .unwrap_or_else(|_| Version::parse("0.0.0").unwrap())
-                > self
-                    .sources
-                    .get(extension)
-                    .unwrap()
-                    .version
-                    .parse::<Version>()
-                    .unwrap()
-            {
-                self.sources.insert(
-                    extension.to_string(),
-                    repository_versioning
-                        .sources
-                        .get(extension)
-                        .unwrap()
-                        .clone(),
+            else {
+                error!(
+                    "The version of the {} extension is not valid semver, ignoring it",
+                    extension
                 );
+                continue;
+            };
 
-                let updated_extension = metadata
-                    .repositories
-                    .get_mut(
-                        env::var("REPOSITORY")
-                            .unwrap()
-                            .strip_prefix("paperback-community/")
-                            .unwrap(),
+            // A registry version which can't be parsed is replaced by any valid version
+            let registry_version = self
+                .sources
+                .get(extension)
+                .unwrap()
+                .version
+                .parse::<Version>()
+                .inspect_err(|_| {
+                    warn!(
+                        "The version of the {} extension in the registry is not valid semver, replacing it",
+                        extension
                     )
-                    .unwrap()
-                    .extensions
-                    .get_mut(extension)
-                    .unwrap();
-
-                updated_extension
-                    .build_time
-                    .clone_from(&repository_versioning.build_time);
-                updated_extension
-                    .built_with
-                    .clone_from(&repository_versioning.built_with);
-
-                managed_extensions.push((extension.clone(), ManageTypes::Update, HashMap::new()));
+                })
+                .ok();
+
+            /*
+             * Versions are ordered by semver precedence, so a prerelease comes before the release
+             * it precedes (1.2.0-beta.1 < 1.2.0) and build metadata is ignored. Lower versions only
+             * replace the registry copy when a rollback was requested.
+             */
+            let manage_type = match registry_version {
+                None => ManageTypes::Update,
+                Some(registry_version) if repository_version > registry_version => {
+                    ManageTypes::Update
+                }
+                Some(registry_version)
+                    if repository_version < registry_version && rollbacks.contains(extension) =>
+                {
+                    ManageTypes::Rollback
+                }
+                Some(registry_version)
+                    if repository_version == registry_version
+                        && content_changed((repository_files, registry_files), extension) =>
+                {
+                    // Published versions are expected to be immutable
+                    changed_extensions.push(extension.clone());
+                    if changed_content != ChangedContent::Update {
+                        warn!(
+                            "The content of the {} extension changed without a version bump, ignoring it",
+                            extension
+                        );
+                        continue;
+                    }
+
+                    warn!(
+                        "The content of the {} extension changed without a version bump, updating it anyway",
+                        extension
+                    );
+                    ManageTypes::Update
+                }
+                _ => continue,
+            };
+
+            let source = repository_versioning.sources.get(extension).unwrap();
+            if !admissible(
+                source,
+                blocklist,
+                &utils::env::repository_name(),
+                repository_files,
+            ) {
+                continue;
             }
+
+            self.sources.insert(extension.to_string(), source.clone());
+
+            let updated_extension = metadata
+                .repositories
+                .get_mut(&utils::env::repository_name())
+                .unwrap()
+                .extensions
+                .get_mut(extension)
+                .unwrap();
+
+            updated_extension
+                .build_time
+                .clone_from(&repository_versioning.build_time);
+            updated_extension
+                .built_with
+                .clone_from(&repository_versioning.built_with);
+
+            managed_extensions.push((extension.clone(), manage_type, HashMap::new()));
         }
+
+        if changed_content == ChangedContent::Fail && !changed_extensions.is_empty() {
+            return Err(Error::ChangedContent {
+                ids: changed_extensions,
+            });
+        }
+
+        Ok(())
     }
 
     fn extension_deletions(
@@ -410,41 +715,426 @@ impl Versioning {
         for extension in registry_extensions {
             self.sources.remove(extension);
 
-            metadata
-                .repositories
-                .get_mut(
-                    env::var("REPOSITORY")
-                        .unwrap()
-                        .strip_prefix("paperback-community/")
-                        .unwrap(),
-                )
-                .unwrap()
-                .extensions
-                .remove(extension);
+            metadata.remove(&utils::env::repository_name(), extension);
 
-            if metadata
-                .repositories
-                .get(
-                    env::var("REPOSITORY")
-                        .unwrap()
-                        .strip_prefix("paperback-community/")
-                        .unwrap(),
-                )
-                .unwrap()
-                .extensions
-                .is_empty()
+            managed_extensions.push((extension.clone(), ManageTypes::Deletion, HashMap::new()));
+        }
+    }
+}
+
+impl Source {
+    /*
+     * Every intent of the source, whether its capabilities are listed separately or combined.
+     */
+    fn intents(&self) -> SourceIntents {
+        match &self.capabilities {
+            Some(Capabilities::List(intents)) => intents
+                .iter()
+                .fold(SourceIntents::empty(), |all, intent| all | *intent),
+            Some(Capabilities::Primitive(intents)) => *intents,
+            None => SourceIntents::empty(),
+        }
+    }
+
+    /*
+     * Checks the fields of a source of the current repository against its files, returning the
+     * problems per field.
+     */
+    fn validate(&self, repository_files: &BTreeMap<String, String>) -> Vec<(&'static str, String)> {
+        let mut errors = vec![];
+        let branch = env::var("BRANCH").unwrap();
+        let directory = format!("{branch}/{}/", self.id);
+
+        if !is_slug(&self.id) {
+            errors.push((
+                "id",
+                format!(
+                    "\"{}\" may only contain letters, digits, dashes and underscores",
+                    self.id
+                ),
+            ));
+        } else if !repository_files
+            .range(directory.clone()..)
+            .next()
+            .is_some_and(|(path, _)| path.starts_with(&directory))
+        {
+            errors.push(("id", format!("there is no {directory} directory")));
+        }
+
+        // Prereleases and testing badges are only published to testing branches
+        let testing = branch.ends_with("/testing");
+
+        match self.version.parse::<Version>() {
+            Err(_) => errors.push((
+                "version",
+                format!("\"{}\" is not valid semver", self.version),
+            )),
+            Ok(version) if version.is_prerelease() && !testing => errors.push((
+                "version",
+                format!(
+                    "\"{}\" is a prerelease, which can only be published to testing branches",
+                    self.version
+                ),
+            )),
+            Ok(_) => (),
+        }
+
+        if self.icon.is_empty()
+            || !repository_files.contains_key(&format!("{directory}static/{}", self.icon))
+        {
+            errors.push(("icon", format!("static/{} does not exist", self.icon)));
+        }
+
+        if let ContentRating::Other(content_rating) = &self.content_rating {
+            errors.push((
+                "contentRating",
+                format!("\"{content_rating}\" is not one of SAFE, MATURE or ADULT"),
+            ));
+        }
+
+        if let Some(language) = &self.language
+            && !language.is_valid()
+        {
+            errors.push((
+                "language",
+                format!("\"{}\" is not a valid language tag", language.0),
+            ));
+        }
+
+        if self.badges.iter().any(Option::is_none) {
+            errors.push(("badges", String::from("it contains null entries")));
+        } else if !testing {
+            let testing_badges = utils::env::testing_badges();
+            if let Some(badge) = self
+                .badges
+                .iter()
+                .flatten()
+                .find(|badge| testing_badges.contains(&badge.label.to_lowercase()))
             {
-                metadata.repositories.remove(
-                    env::var("REPOSITORY")
-                        .unwrap()
-                        .strip_prefix("paperback-community/")
-                        .unwrap(),
-                );
+                errors.push((
+                    "badges",
+                    format!("\"{}\" can only be used on testing branches", badge.label),
+                ));
             }
+        }
 
-            managed_extensions.push((extension.clone(), ManageTypes::Deletion, HashMap::new()));
+        if self.developers.iter().any(Option::is_none) {
+            errors.push(("developers", String::from("it contains null entries")));
         }
+
+        errors
     }
 }
 
-impl JsonFileAsStruct for Metadata {}
+impl From<String> for ContentRating {
+    fn from(content_rating: String) -> ContentRating {
+        match content_rating.as_str() {
+            "SAFE" => ContentRating::Everyone,
+            "MATURE" => ContentRating::Mature,
+            "ADULT" => ContentRating::Adult,
+            _ => ContentRating::Other(content_rating),
+        }
+    }
+}
+
+impl From<ContentRating> for String {
+    fn from(content_rating: ContentRating) -> String {
+        match content_rating {
+            ContentRating::Everyone => String::from("SAFE"),
+            ContentRating::Mature => String::from("MATURE"),
+            ContentRating::Adult => String::from("ADULT"),
+            ContentRating::Other(content_rating) => content_rating,
+        }
+    }
+}
+
+impl Language {
+    /*
+     * A BCP 47 style tag starting with an ISO 639 language code, such as "en" or "pt-BR".
+     */
+    fn is_valid(&self) -> bool {
+        let mut subtags = self.0.split('-');
+
+        subtags.next().is_some_and(|subtag| {
+            (2..=3).contains(&subtag.len())
+                && subtag
+                    .chars()
+                    .all(|character| character.is_ascii_lowercase())
+        }) && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len())
+                && subtag
+                    .chars()
+                    .all(|character| character.is_ascii_alphanumeric())
+        })
+    }
+}
+
+impl Serialize for SourceIntents {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.bits().serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for SourceIntents {
+    fn deserialize<D>(deserializer: D) -> Result<SourceIntents, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(SourceIntents::from_bits_retain(u32::deserialize(
+            deserializer,
+        )?))
+    }
+}
+
+/*
+ * Whether the files of the extension in the registry differ from the ones in the repository.
+ * Files which only exist in the repository aren't a change, before nested files were synced the
+ * registry only received index.js and the top level static files, so they were never published.
+ */
+fn content_changed(
+    (repository_files, registry_files): (&BTreeMap<String, String>, &BTreeMap<String, String>),
+    extension: &str,
+) -> bool {
+    let directory = env::var("BRANCH").unwrap() + "/" + extension;
+    let repository_files = utils::git::directory_files(repository_files, &directory);
+
+    utils::git::directory_files(registry_files, &directory)
+        .iter()
+        .any(|(path, sha)| repository_files.get(path) != Some(sha))
+}
+
+/*
+ * Whether the source of the given repository may be added to or updated in the registry, logging
+ * why it may not.
+ */
+fn admissible(
+    source: &Source,
+    blocklist: &Blocklist,
+    repository: &str,
+    repository_files: &BTreeMap<String, String>,
+) -> bool {
+    if let Some(reason) = blocklist.blocked(source, repository) {
+        warn!(
+            "The {} extension is blocked from the registry ({}), ignoring it",
+            source.id, reason
+        );
+        return false;
+    }
+
+    let errors = source.validate(repository_files);
+    if !errors.is_empty() {
+        error!(
+            "The {} extension is invalid, ignoring it: {}",
+            source.id,
+            errors
+                .iter()
+                .map(|(field, message)| format!("{field}: {message}"))
+                .collect::<Vec<String>>()
+                .join("; ")
+        );
+        return false;
+    }
+
+    true
+}
+
+fn is_slug(id: &str) -> bool {
+    id.starts_with(|character: char| character.is_ascii_alphanumeric())
+        && id.chars().all(|character| {
+            character.is_ascii_alphanumeric() || character == '-' || character == '_'
+        })
+}
+
+impl JsonFileAsStruct for Metadata {
+    const FILE_NAME: &'static str = "metadata.json";
+}
+
+impl Metadata {
+    pub fn repositories(&self) -> impl Iterator<Item = &String> {
+        self.repositories.keys()
+    }
+
+    pub fn extension(&self, repository: &str, id: &str) -> Option<&MetadataExtension> {
+        self.repositories.get(repository)?.extensions.get(id)
+    }
+
+    pub fn insert(&mut self, repository: &str, id: &str, extension: MetadataExtension) {
+        self.repositories
+            .entry(repository.to_string())
+            .or_default()
+            .extensions
+            .insert(id.to_string(), extension);
+    }
+
+    /*
+     * Removes the extension from the repository, together with the repository once it has no
+     * extensions left.
+     */
+    pub fn remove(&mut self, repository: &str, id: &str) {
+        let Some(metadata) = self.repositories.get_mut(repository) else {
+            return;
+        };
+
+        metadata.extensions.remove(id);
+        if metadata.extensions.is_empty() {
+            self.repositories.remove(repository);
+        }
+    }
+
+    /*
+     * The repository the extension belongs to.
+     */
+    pub fn owner(&self, id: &str) -> Option<&String> {
+        self.repositories
+            .iter()
+            .find(|(_, metadata)| metadata.extensions.contains_key(id))
+            .map(|(repository, _)| repository)
+    }
+
+    /*
+     * Every extension in the metadata file, together with the name of the repository it belongs
+     * to.
+     */
+    pub fn extensions(&self) -> impl Iterator<Item = (&String, &String)> {
+        self.repositories.iter().flat_map(|(repository, metadata)| {
+            metadata
+                .extensions
+                .keys()
+                .map(move |extension| (repository, extension))
+        })
+    }
+}
+
+impl MetadataExtension {
+    /*
+     * The metadata of an extension which was published with the given versioning file.
+     */
+    pub fn new(versioning: &Versioning) -> MetadataExtension {
+        MetadataExtension {
+            build_time: versioning.build_time.clone(),
+            built_with: versioning.built_with.clone(),
+        }
+    }
+}
+
+impl JsonFileAsStruct for Transfers {
+    const FILE_NAME: &'static str = "transfers.json";
+}
+
+impl Transfers {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /*
+     * Removes the transfer of the extension between the given repositories, returning whether
+     * such a transfer was allowed.
+     */
+    fn take(&mut self, id: &str, from: &str, to: &str) -> bool {
+        let Some(index) = self
+            .0
+            .iter()
+            .position(|transfer| transfer.id == id && transfer.from == from && transfer.to == to)
+        else {
+            return false;
+        };
+
+        self.0.remove(index);
+        true
+    }
+}
+
+impl JsonFileAsStruct for Takedowns {
+    const FILE_NAME: &'static str = "takedowns.json";
+}
+
+impl Takedowns {
+    pub fn contains(&self, id: &str) -> bool {
+        self.0.iter().any(|takedown| takedown.id == id)
+    }
+
+    /*
+     * Records the takedown of the extension, returning false when it was already taken down.
+     */
+    pub fn insert(&mut self, id: &str, repository: Option<String>, reason: Option<String>) -> bool {
+        if self.contains(id) {
+            return false;
+        }
+
+        self.0.push(Takedown {
+            id: id.to_string(),
+            repository,
+            reason,
+            time: Utc::now().format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string(),
+        });
+        true
+    }
+}
+
+impl JsonFileAsStruct for Blocklist {
+    const FILE_NAME: &'static str = "blocklist.json";
+}
+
+impl Default for Blocklist {
+    fn default() -> Blocklist {
+        Blocklist::from(vec![])
+    }
+}
+
+impl From<Vec<BlockRule>> for Blocklist {
+    fn from(rules: Vec<BlockRule>) -> Blocklist {
+        let template = BlockRule {
+            id: None,
+            id_suffix: Some(String::from("Template")),
+            repository: None,
+            versions: None,
+            content_rating: None,
+            intents: None,
+            reason: String::from("template extensions are not published"),
+        };
+
+        Blocklist([vec![template], rules].concat())
+    }
+}
+
+impl Blocklist {
+    /*
+     * The reason of the first rule blocking the source of the given repository.
+     */
+    pub fn blocked(&self, source: &Source, repository: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|rule| rule.matches(source, repository))
+            .map(|rule| rule.reason.as_str())
+    }
+}
+
+impl BlockRule {
+    fn matches(&self, source: &Source, repository: &str) -> bool {
+        self.id.as_ref().is_none_or(|id| *id == source.id)
+            && self
+                .id_suffix
+                .as_ref()
+                .is_none_or(|id_suffix| source.id.ends_with(id_suffix))
+            && self
+                .repository
+                .as_ref()
+                .is_none_or(|blocked_repository| blocked_repository == repository)
+            && self.versions.as_ref().is_none_or(|versions| {
+                source
+                    .version
+                    .parse::<Version>()
+                    .is_ok_and(|version| version.satisfies(versions))
+            })
+            && self
+                .content_rating
+                .as_ref()
+                .is_none_or(|content_rating| *content_rating == source.content_rating)
+            && self
+                .intents
+                .is_none_or(|intents| source.intents().contains(intents))
+    }
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use common::{Backend, MockGitHub, REPOSITORY, registry_branch, source, versioning};
+use serde_json::json;
+
+fn repository(github: &MockGitHub, repository_branch: &str, branch: &str) {
+    github.add_json(
+        REPOSITORY,
+        repository_branch,
+        &format!("{branch}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    github.add_file(
+        REPOSITORY,
+        repository_branch,
+        &format!("{branch}/Foo/index.js"),
+        b"// Foo",
+    );
+    github.add_file(
+        REPOSITORY,
+        repository_branch,
+        &format!("{branch}/Foo/static/icon.png"),
+        b"icon",
+    );
+}
+
+#[test]
+fn flags_override_environment() {
+    let github = MockGitHub::start();
+    registry_branch(&github, "0.9/testing", &[], &json!({}));
+    repository(&github, "pages", "0.9/testing");
+
+    let output = github.run_command(
+        &[
+            "sync",
+            "--branch",
+            "0.9/testing",
+            "--repository-branch",
+            "pages",
+            "--commit-message",
+            "Publish Foo",
+        ],
+        &[],
+    );
+    assert!(output.status.success());
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/testing/Foo/index.js",
+            "0.9/testing/Foo/static/icon.png",
+            "0.9/testing/metadata.json",
+            "0.9/testing/versioning.json",
+        ]
+    );
+    assert_eq!(github.state().commits[0]["message"], "Publish Foo");
+}
+
+#[test]
+fn diff() {
+    let github = MockGitHub::start();
+    registry_branch(&github, "0.9/stable", &[], &json!({}));
+    registry_branch(
+        &github,
+        "0.9/testing",
+        &[source("Foo", "1.0.0")],
+        &json!({}),
+    );
+    repository(&github, "gh-pages", "0.9/stable");
+
+    let output = github.run_command(&["diff"], &[]);
+    assert!(output.status.success());
+
+    let state = github.state();
+    assert!(state.commits.is_empty());
+    assert!(state.references.is_empty());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("add     0.9/stable/Foo/index.js"));
+    assert!(stdout.contains("change  0.9/stable/versioning.json"));
+}
+
+#[test]
+fn unknown_command() {
+    let github = MockGitHub::start();
+
+    let output = github.run_command(&["publish"], &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unrecognized subcommand 'publish'"));
+}
+
+#[test]
+fn missing_token() {
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_registry-manager"))
+        .arg("verify")
+        .env_clear()
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("The GITHUB_TOKEN environment variable is invalid: it was not set")
+    );
+}
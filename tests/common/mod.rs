@@ -0,0 +1,485 @@
+#![allow(dead_code)]
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    process::{Command, Output},
+    sync::{Arc, Mutex},
+    thread::{self, JoinHandle},
+};
+
+use base64::{Engine, prelude::BASE64_STANDARD};
+use serde_json::{Value, json};
+use sha1::{Digest, Sha1};
+use tiny_http::{Header, Method, Request, Response, Server};
+
+pub const REGISTRY: &str = "paperback-community/extensions";
+pub const REPOSITORY: &str = "paperback-community/community-extensions";
+pub const BRANCH: &str = "0.9/stable";
+pub const TESTING: &str = "0.9/testing";
+pub const BASE_COMMIT: &str = "base-commit-sha";
+pub const BASE_TREE: &str = "base-tree-sha";
+pub const MOVED_COMMIT: &str = "moved-commit-sha";
+pub const MOVED_TREE: &str = "moved-tree-sha";
+
+struct Failure {
+    path: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    message: String,
+}
+
+#[derive(Default)]
+pub struct State {
+    files: HashMap<(String, String), BTreeMap<String, Vec<u8>>>,
+    heads: HashMap<(String, String), String>,
+    moved: bool,
+    concurrent_files: Vec<(String, Vec<u8>)>,
+    failures: Vec<Failure>,
+    pub blobs: Vec<Value>,
+    pub trees: Vec<Value>,
+    pub commits: Vec<Value>,
+    pub references: Vec<(String, Value)>,
+}
+
+/*
+ * Where the fixtures of a test are written and the tool runs against, either the mock GitHub API
+ * or a directory for the local backend.
+ */
+pub trait Backend {
+    fn add_file(&self, repository: &str, branch: &str, path: &str, content: &[u8]);
+
+    /*
+     * The environment variables which point the tool to the backend.
+     */
+    fn environment(&self) -> Vec<(&'static str, String)>;
+
+    fn add_json(&self, repository: &str, branch: &str, path: &str, content: &Value) {
+        self.add_file(
+            repository,
+            branch,
+            path,
+            serde_json::to_string(content).unwrap().as_bytes(),
+        );
+    }
+
+    fn run(&self, env: &[(&str, &str)]) -> Output {
+        self.run_command(&[], env)
+    }
+
+    fn run_command(&self, args: &[&str], env: &[(&str, &str)]) -> Output {
+        let output = Command::new(env!("CARGO_BIN_EXE_registry-manager"))
+            .args(args)
+            .env_clear()
+            .envs(self.environment())
+            .env("REPOSITORY", REPOSITORY)
+            .env("BRANCH", BRANCH)
+            .env("NO_COLOR", "1")
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+
+        println!("{}", String::from_utf8_lossy(&output.stdout));
+        eprintln!("{}", String::from_utf8_lossy(&output.stderr));
+
+        output
+    }
+}
+
+/*
+ * A stand-in for the parts of api.github.com the tool uses, serving files from memory and
+ * recording everything the tool writes to the registry.
+ */
+pub struct MockGitHub {
+    pub url: String,
+    state: Arc<Mutex<State>>,
+    server: Arc<Server>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl MockGitHub {
+    pub fn start() -> MockGitHub {
+        let server = Arc::new(Server::http("127.0.0.1:0").unwrap());
+        let url = format!(
+            "http://127.0.0.1:{}",
+            server.server_addr().to_ip().unwrap().port()
+        );
+        let state = Arc::new(Mutex::new(State::default()));
+
+        let handle = {
+            let server = Arc::clone(&server);
+            let state = Arc::clone(&state);
+            thread::spawn(move || {
+                for request in server.incoming_requests() {
+                    handle(&state, request);
+                }
+            })
+        };
+
+        MockGitHub {
+            url,
+            state,
+            server,
+            handle: Some(handle),
+        }
+    }
+
+    /*
+     * Simulates another run updating the registry, the first reference update gets rejected
+     * after which the given file is written to the registry and its branch moves.
+     */
+    pub fn add_concurrent_json(&self, path: &str, content: &Value) {
+        self.state().concurrent_files.push((
+            path.to_string(),
+            serde_json::to_string(content).unwrap().into_bytes(),
+        ));
+    }
+
+    /*
+     * Makes the next request whose URL contains the given path fail with the given status,
+     * headers and message. Failures for the same path are used up in the order they were added.
+     */
+    pub fn add_failure(&self, path: &str, status: u16, headers: &[(&str, &str)], message: &str) {
+        self.state().failures.push(Failure {
+            path: path.to_string(),
+            status,
+            headers: headers
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            message: message.to_string(),
+        });
+    }
+
+    pub fn state(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap()
+    }
+
+    /*
+     * Returns the entries of the created tree, mapped from their path to their SHA (null for
+     * deletions).
+     */
+    pub fn tree_entries(&self) -> BTreeMap<String, Value> {
+        let state = self.state();
+        assert_eq!(state.trees.len(), 1, "expected exactly one created tree");
+
+        state.trees[0]["tree"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|entry| {
+                (
+                    entry["path"].as_str().unwrap().to_string(),
+                    entry["sha"].clone(),
+                )
+            })
+            .collect()
+    }
+
+    /*
+     * Returns the decoded content of the blob with the given SHA.
+     */
+    pub fn blob(&self, sha: &Value) -> Vec<u8> {
+        let state = self.state();
+        let index = sha
+            .as_str()
+            .unwrap()
+            .strip_prefix("blob-")
+            .unwrap()
+            .parse::<usize>()
+            .unwrap();
+        let blob = &state.blobs[index];
+
+        let content = blob["content"].as_str().unwrap();
+        if blob["encoding"] == "base64" {
+            BASE64_STANDARD.decode(content).unwrap()
+        } else {
+            content.as_bytes().to_vec()
+        }
+    }
+
+    pub fn blob_json(&self, sha: &Value) -> Value {
+        serde_json::from_slice(&self.blob(sha)).unwrap()
+    }
+}
+
+impl Backend for MockGitHub {
+    fn add_file(&self, repository: &str, branch: &str, path: &str, content: &[u8]) {
+        self.state()
+            .files
+            .entry((repository.to_string(), branch.to_string()))
+            .or_default()
+            .insert(path.to_string(), content.to_vec());
+    }
+
+    fn environment(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("GITHUB_API_URL", self.url.clone()),
+            ("GITHUB_TOKEN", String::from("test-token")),
+        ]
+    }
+}
+
+impl Drop for MockGitHub {
+    fn drop(&mut self) {
+        self.server.unblock();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+fn handle(state: &Mutex<State>, mut request: Request) {
+    let mut body = String::new();
+    let _ = request.as_reader().read_to_string(&mut body);
+
+    {
+        let mut state = state.lock().unwrap();
+        if let Some(index) = state
+            .failures
+            .iter()
+            .position(|failure| request.url().contains(&failure.path))
+        {
+            let failure = state.failures.remove(index);
+            let mut response =
+                Response::from_string(json!({ "message": failure.message }).to_string())
+                    .with_status_code(failure.status);
+            for (name, value) in failure.headers {
+                response.add_header(Header::from_bytes(name, value).unwrap());
+            }
+            let _ = request.respond(response);
+            return;
+        }
+    }
+
+    let (path, query) = request.url().split_once('?').map_or(
+        (request.url().to_string(), String::new()),
+        |(path, query)| (path.to_string(), query.to_string()),
+    );
+    let reference = query
+        .split('&')
+        .find_map(|parameter| parameter.strip_prefix("ref="))
+        .unwrap_or("")
+        .to_string();
+
+    let segments = path
+        .trim_start_matches("/repos/")
+        .splitn(4, '/')
+        .collect::<Vec<&str>>();
+    if segments.len() < 3 {
+        return respond(request, 404, json!({ "message": "Not Found" }));
+    }
+    let repository = format!("{}/{}", segments[0], segments[1]);
+    let rest = segments[2..].join("/");
+
+    let mut state = state.lock().unwrap();
+    let method = request.method().clone();
+
+    match (method, rest.split_once('/')) {
+        (Method::Get, Some(("contents", file_path))) => {
+            match files(&state, &repository, &reference).and_then(|files| files.get(file_path)) {
+                Some(content) => {
+                    let _ = request.respond(Response::from_data(content.clone()));
+                }
+                None => respond(request, 404, json!({ "message": "Not Found" })),
+            }
+        }
+        (Method::Get, Some(("git", tree))) if tree.starts_with("trees/") => {
+            let Some(files) = files(&state, &repository, &tree["trees/".len()..]) else {
+                return respond(request, 404, json!({ "message": "Not Found" }));
+            };
+
+            let mut entries = BTreeMap::new();
+            for (path, content) in files {
+                entries.insert(path.clone(), ("blob", blob_sha(content)));
+
+                let mut directory = path.as_str();
+                while let Some((parent, _)) = directory.rsplit_once('/') {
+                    entries.insert(parent.to_string(), ("tree", format!("tree-of-{parent}")));
+                    directory = parent;
+                }
+            }
+
+            respond(
+                request,
+                200,
+                json!({
+                    "sha": format!("tree-of-{repository}"),
+                    "tree": entries
+                        .into_iter()
+                        .map(|(path, (etype, sha))| {
+                            json!({ "path": path, "mode": "100644", "type": etype, "sha": sha })
+                        })
+                        .collect::<Vec<Value>>(),
+                    "truncated": false
+                }),
+            );
+        }
+        (Method::Get, Some(("branches", branch))) => {
+            let (commit, tree) = if state.moved {
+                (MOVED_COMMIT, MOVED_TREE)
+            } else {
+                (BASE_COMMIT, BASE_TREE)
+            };
+            state
+                .heads
+                .insert((repository, commit.to_string()), branch.to_string());
+
+            respond(
+                request,
+                200,
+                json!({ "commit": { "sha": commit, "commit": { "tree": { "sha": tree } } } }),
+            );
+        }
+        (Method::Post, Some(("git", "blobs"))) => {
+            let sha = format!("blob-{}", state.blobs.len());
+            state.blobs.push(serde_json::from_str(&body).unwrap());
+            respond(request, 201, json!({ "sha": sha }));
+        }
+        (Method::Post, Some(("git", "trees"))) => {
+            let sha = format!("tree-{}", state.trees.len());
+            state.trees.push(serde_json::from_str(&body).unwrap());
+            respond(request, 201, json!({ "sha": sha }));
+        }
+        (Method::Post, Some(("git", "commits"))) => {
+            let sha = format!("commit-{}", state.commits.len());
+            state.commits.push(serde_json::from_str(&body).unwrap());
+            respond(request, 201, json!({ "sha": sha }));
+        }
+        (Method::Patch, Some(("git", reference)))
+            if reference.starts_with("refs/") && !state.concurrent_files.is_empty() =>
+        {
+            let concurrent_files = std::mem::take(&mut state.concurrent_files);
+            let files = state
+                .files
+                .entry((REGISTRY.to_string(), String::from("master")))
+                .or_default();
+            for (path, content) in concurrent_files {
+                files.insert(path, content);
+            }
+            state.moved = true;
+
+            respond(
+                request,
+                422,
+                json!({ "message": "Update is not a fast forward" }),
+            );
+        }
+        (Method::Patch, Some(("git", reference))) if reference.starts_with("refs/") => {
+            state.references.push((
+                format!("{repository}/{reference}"),
+                serde_json::from_str(&body).unwrap(),
+            ));
+            respond(request, 200, json!({ "ref": reference }));
+        }
+        _ => respond(request, 404, json!({ "message": "Not Found" })),
+    }
+}
+
+/*
+ * Returns the files of the given branch or commit. Files added at a commit take precedence over
+ * the ones of the branch it is the head of.
+ */
+fn files<'s>(
+    state: &'s State,
+    repository: &str,
+    reference: &str,
+) -> Option<&'s BTreeMap<String, Vec<u8>>> {
+    state
+        .files
+        .get(&(repository.to_string(), reference.to_string()))
+        .or_else(|| {
+            let branch = state
+                .heads
+                .get(&(repository.to_string(), reference.to_string()))?;
+            state.files.get(&(repository.to_string(), branch.clone()))
+        })
+}
+
+/*
+ * The SHA Git assigns to a blob with the given content, as returned by the contents API.
+ */
+pub fn blob_sha(content: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(format!("blob {}\0", content.len()).as_bytes());
+    hasher.update(content);
+    hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+fn respond(request: Request, status: u16, body: Value) {
+    let response = Response::from_string(body.to_string())
+        .with_status_code(status)
+        .with_header(Header::from_bytes("Content-Type", "application/json").unwrap());
+    let _ = request.respond(response);
+}
+
+pub fn source(id: &str, version: &str) -> Value {
+    json!({
+        "id": id,
+        "name": id,
+        "description": format!("The {id} extension"),
+        "version": version,
+        "icon": "icon.png",
+        "language": "en",
+        "contentRating": "SAFE",
+        "badges": [],
+        "capabilities": [1, 2],
+        "developers": [{ "name": "Developer", "website": null, "github": null }]
+    })
+}
+
+pub fn versioning(sources: &[Value]) -> Value {
+    json!({
+        "buildTime": "2025-04-21T00:00:00.000Z",
+        "builtWith": { "toolchain": "1.0.0", "types": "1.0.0-alpha.20" },
+        "repository": { "name": "Community Extensions", "description": "Extensions" },
+        "sources": sources
+    })
+}
+
+pub fn metadata_extension() -> Value {
+    json!({
+        "build_time": "2025-04-21T00:00:00.000Z",
+        "built_with": { "toolchain": "1.0.0", "types": "1.0.0-alpha.20" }
+    })
+}
+
+/*
+ * Writes the versioning and metadata files of a branch of the registry.
+ */
+pub fn registry_branch(backend: &impl Backend, branch: &str, sources: &[Value], metadata: &Value) {
+    backend.add_json(
+        REGISTRY,
+        "master",
+        &format!("{branch}/versioning.json"),
+        &versioning(sources),
+    );
+    backend.add_json(
+        REGISTRY,
+        "master",
+        &format!("{branch}/metadata.json"),
+        metadata,
+    );
+}
+
+/*
+ * Publishes an extension with an index.js and a single static icon on the gh-pages branch of
+ * the given repository.
+ */
+pub fn publish_extension(backend: &impl Backend, repository: &str, branch: &str, id: &str) {
+    backend.add_file(
+        repository,
+        branch,
+        &format!("{BRANCH}/{id}/index.js"),
+        format!("// {id} {branch}").as_bytes(),
+    );
+    backend.add_file(
+        repository,
+        branch,
+        &format!("{BRANCH}/{id}/static/icon.png"),
+        &[0x89, b'P', b'N', b'G', 0x00, 0xff],
+    );
+}
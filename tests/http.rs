@@ -0,0 +1,129 @@
+mod common;
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use common::{
+    BRANCH, Backend, MockGitHub, REPOSITORY, TESTING, metadata_extension, publish_extension,
+    registry_branch, source, versioning,
+};
+use serde_json::json;
+
+// Keeps the retries of the tests fast
+const BACKOFF: (&str, &str) = ("HTTP_BACKOFF", "1");
+// Makes sure no other blobs get created while a failing one is being retried
+const SEQUENTIAL: (&str, &str) = ("CONCURRENCY", "1");
+
+fn addition(github: &MockGitHub) {
+    registry_branch(github, BRANCH, &[], &json!({}));
+    registry_branch(github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(github, REPOSITORY, "gh-pages", "Foo");
+}
+
+fn assert_committed(github: &MockGitHub) {
+    let state = github.state();
+    assert_eq!(state.commits.len(), 1);
+    assert_eq!(state.references.len(), 1);
+}
+
+#[test]
+fn server_errors_are_retried() {
+    let github = MockGitHub::start();
+    addition(&github);
+    github.add_failure("/branches/", 502, &[], "Bad Gateway");
+    github.add_failure("/contents/", 503, &[], "Service Unavailable");
+    github.add_failure("/git/blobs", 500, &[], "Internal Server Error");
+    github.add_failure("/git/blobs", 502, &[], "Bad Gateway");
+
+    assert!(github.run(&[BACKOFF]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/index.js"]),
+        b"// Foo gh-pages"
+    );
+    assert_eq!(
+        github.blob_json(&tree["0.9/stable/metadata.json"]),
+        json!({ "community-extensions": { "Foo": metadata_extension() } })
+    );
+}
+
+#[test]
+fn exhausted_retries() {
+    let github = MockGitHub::start();
+    addition(&github);
+    for _ in 0..3 {
+        github.add_failure("/git/trees", 502, &[], "Bad Gateway");
+    }
+
+    let output = github.run(&[BACKOFF, ("HTTP_RETRIES", "2")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("status code: 502 Bad Gateway"));
+
+    let state = github.state();
+    assert!(state.commits.is_empty());
+    assert!(state.references.is_empty());
+}
+
+#[test]
+fn client_errors_are_not_retried() {
+    let github = MockGitHub::start();
+    addition(&github);
+    github.add_failure("/git/blobs", 401, &[], "Bad credentials");
+
+    assert!(!github.run(&[BACKOFF, SEQUENTIAL]).status.success());
+    assert!(github.state().blobs.is_empty());
+}
+
+#[test]
+fn rate_limit_is_waited_out() {
+    let github = MockGitHub::start();
+    addition(&github);
+    let reset = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+        .to_string();
+    github.add_failure(
+        "/contents/",
+        403,
+        &[
+            ("X-RateLimit-Remaining", "0"),
+            ("X-RateLimit-Reset", &reset),
+        ],
+        "API rate limit exceeded",
+    );
+    github.add_failure(
+        "/git/commits",
+        429,
+        &[("Retry-After", "0")],
+        "You have exceeded a secondary rate limit",
+    );
+
+    assert!(github.run(&[BACKOFF]).status.success());
+    assert_committed(&github);
+}
+
+#[test]
+fn rate_limit_resetting_too_late() {
+    let github = MockGitHub::start();
+    addition(&github);
+    // Secondary rate limits without a moment to retry at are waited out for a minute
+    github.add_failure(
+        "/git/blobs",
+        403,
+        &[],
+        "You have exceeded a secondary rate limit",
+    );
+
+    let output = github.run(&[BACKOFF, SEQUENTIAL, ("RATE_LIMIT_MAX_WAIT", "10")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("rate limit was exceeded"));
+    assert!(github.state().blobs.is_empty());
+}
@@ -0,0 +1,170 @@
+mod common;
+
+use std::{fs, path::PathBuf};
+
+use common::{
+    BRANCH, Backend, REGISTRY, REPOSITORY, TESTING, metadata_extension, publish_extension,
+    registry_branch, source, versioning,
+};
+use serde_json::{Value, json};
+
+/*
+ * A LOCAL_PATH directory holding the registry and repository branches, removed again once the
+ * test is done.
+ */
+struct Directory(PathBuf);
+
+impl Directory {
+    fn new(name: &str) -> Directory {
+        let root =
+            std::env::temp_dir().join(format!("registry-manager-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+
+        Directory(root)
+    }
+
+    fn path(&self, repository: &str, branch: &str, path: &str) -> PathBuf {
+        self.0.join(repository).join(branch).join(path)
+    }
+
+    fn registry_json(&self, path: &str) -> Value {
+        serde_json::from_slice(&fs::read(self.path(REGISTRY, "master", path)).unwrap()).unwrap()
+    }
+}
+
+impl Backend for Directory {
+    fn add_file(&self, repository: &str, branch: &str, path: &str, content: &[u8]) {
+        let path = self.path(repository, branch, path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn environment(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("BACKEND", String::from("local")),
+            ("LOCAL_PATH", self.0.display().to_string()),
+        ]
+    }
+}
+
+impl Drop for Directory {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+#[test]
+fn sync() {
+    let directory = Directory::new("sync");
+    registry_branch(&directory, BRANCH, &[], &json!({}));
+    registry_branch(&directory, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    directory.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(&directory, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(directory.run_command(&["sync"], &[]).status.success());
+
+    assert_eq!(
+        fs::read(directory.path(REGISTRY, "master", &format!("{BRANCH}/Foo/index.js"))).unwrap(),
+        b"// Foo gh-pages"
+    );
+    assert!(
+        directory
+            .path(REGISTRY, "master", &format!("{BRANCH}/Foo/static/icon.png"))
+            .is_file()
+    );
+    assert_eq!(
+        directory.registry_json(&format!("{BRANCH}/versioning.json"))["sources"],
+        json!([source("Foo", "1.0.0")])
+    );
+    assert_eq!(
+        directory.registry_json(&format!("{BRANCH}/metadata.json"))["community-extensions"]["Foo"]
+            ["built_with"],
+        metadata_extension()["built_with"]
+    );
+}
+
+#[test]
+fn remove() {
+    let directory = Directory::new("remove");
+    registry_branch(
+        &directory,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    publish_extension(&directory, REGISTRY, "master", "Foo");
+
+    assert!(
+        directory
+            .run_command(&["remove", "Foo"], &[])
+            .status
+            .success()
+    );
+
+    // Directories which end up empty are removed together with the files
+    assert!(
+        !directory
+            .path(REGISTRY, "master", &format!("{BRANCH}/Foo"))
+            .exists()
+    );
+    assert_eq!(
+        directory.registry_json(&format!("{BRANCH}/versioning.json"))["sources"],
+        json!([])
+    );
+    assert_eq!(
+        directory.registry_json(&format!("{BRANCH}/takedowns.json"))[0]["id"],
+        "Foo"
+    );
+}
+
+#[test]
+fn promote() {
+    let directory = Directory::new("promote");
+    registry_branch(&directory, BRANCH, &[], &json!({}));
+    registry_branch(
+        &directory,
+        TESTING,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    directory.add_file(
+        REGISTRY,
+        "master",
+        "0.9/testing/Foo/index.js",
+        b"// Foo testing",
+    );
+    directory.add_file(
+        REGISTRY,
+        "master",
+        "0.9/testing/Foo/static/icon.png",
+        b"icon",
+    );
+
+    // The files are promoted by the SHAs of the blobs which already exist in the registry
+    assert!(
+        directory
+            .run_command(&["promote", "Foo"], &[])
+            .status
+            .success()
+    );
+
+    assert_eq!(
+        fs::read(directory.path(REGISTRY, "master", &format!("{BRANCH}/Foo/index.js"))).unwrap(),
+        b"// Foo testing"
+    );
+    assert_eq!(
+        fs::read(directory.path(REGISTRY, "master", &format!("{BRANCH}/Foo/static/icon.png")))
+            .unwrap(),
+        b"icon"
+    );
+    assert_eq!(
+        directory.registry_json(&format!("{BRANCH}/versioning.json"))["sources"],
+        json!([source("Foo", "1.0.0")])
+    );
+}
@@ -0,0 +1,284 @@
+mod common;
+
+use common::{
+    BRANCH, Backend, MockGitHub, REGISTRY, TESTING, blob_sha, metadata_extension,
+    publish_extension, registry_branch, source, versioning,
+};
+use serde_json::{Value, json};
+
+/*
+ * Publishes Foo and Bar with the given sources to the testing branch and Foo 1.0.0 together with
+ * Other to the stable branch, where the stable Foo still has a file which was dropped on testing.
+ */
+fn registry(github: &MockGitHub, testing_sources: &[Value]) {
+    registry_branch(
+        github,
+        TESTING,
+        testing_sources,
+        &json!({
+            "community-extensions": { "Bar": metadata_extension(), "Foo": metadata_extension() }
+        }),
+    );
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{TESTING}/Foo/index.js"),
+        b"// Foo 1.1.0",
+    );
+    for path in ["Bar/index.js", "Bar/static/icon.png"] {
+        github.add_file(
+            REGISTRY,
+            "master",
+            &format!("{TESTING}/{path}"),
+            format!("// {path}").as_bytes(),
+        );
+    }
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{TESTING}/Foo/static/icon.png"),
+        b"Foo icon",
+    );
+
+    registry_branch(
+        github,
+        BRANCH,
+        &[source("Foo", "1.0.0"), source("Other", "1.0.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension() },
+            "other-extensions": { "Other": metadata_extension() }
+        }),
+    );
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Foo/index.js"),
+        b"// Foo 1.0.0",
+    );
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Foo/static/icon.png"),
+        b"Foo icon",
+    );
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Foo/static/banner.png"),
+        b"Foo banner",
+    );
+    publish_extension(github, REGISTRY, "master", "Other");
+}
+
+#[test]
+fn promotion() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.1.0")]);
+
+    let output = github.run_command(&["promote", "Foo"], &[]);
+    assert!(output.status.success());
+
+    let state = github.state();
+    assert_eq!(
+        state.commits[0]["message"],
+        "Registry promotion (Foo, 0.9/testing -> 0.9/stable)"
+    );
+    // Only the versioning and metadata files of both branches are uploaded, the extension files
+    // are reused
+    assert_eq!(state.blobs.len(), 4);
+    drop(state);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/banner.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+            "0.9/testing/metadata.json",
+            "0.9/testing/versioning.json",
+        ]
+    );
+    assert_eq!(tree["0.9/stable/Foo/index.js"], blob_sha(b"// Foo 1.1.0"));
+    assert_eq!(tree["0.9/stable/Foo/static/banner.png"], Value::Null);
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.1.0"), source("Other", "1.0.0")])
+    );
+
+    // The testing branch keeps its extensions, its versioning file is only restamped
+    let testing_versioning = github.blob_json(&tree["0.9/testing/versioning.json"]);
+    assert_eq!(
+        testing_versioning["sources"],
+        json!([source("Foo", "1.1.0")])
+    );
+    assert_eq!(
+        testing_versioning["builtWith"],
+        versioning(&[])["builtWith"]
+    );
+    assert_ne!(
+        testing_versioning["buildTime"],
+        versioning(&[])["buildTime"]
+    );
+    assert_eq!(
+        github.blob_json(&tree["0.9/testing/metadata.json"]),
+        json!({
+            "community-extensions": { "Bar": metadata_extension(), "Foo": metadata_extension() }
+        })
+    );
+}
+
+#[test]
+fn already_promoted() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.0.0")]);
+
+    let output = github.run_command(&["promote", "Foo"], &[]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("The Foo extension was already promoted")
+    );
+    assert!(github.state().commits.is_empty());
+}
+
+#[test]
+fn prerelease_is_not_promoted() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.1.0-beta.1")]);
+
+    let output = github.run_command(&["promote", "Foo"], &[]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("is a prerelease, which can only be published to testing branches"));
+    assert!(stderr.contains(
+        "The Foo extension can't be promoted: it is not admissible on the stable branch"
+    ));
+    assert!(github.state().commits.is_empty());
+}
+
+#[test]
+fn missing_from_testing() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.1.0")]);
+
+    let output = github.run_command(&["promote", "Other"], &[]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            "The Other extension can't be promoted: it is not part of the testing branch"
+        )
+    );
+    assert!(github.state().commits.is_empty());
+}
+
+#[test]
+fn testing_branch() {
+    let github = MockGitHub::start();
+
+    let output = github.run_command(&["promote", "Foo", "--branch", TESTING], &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("it should be a stable branch"));
+}
+
+#[test]
+fn several_extensions() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.1.0"), source("Bar", "1.0.0")]);
+
+    let output = github.run_command(&["promote", "Foo", "Bar,Foo"], &[]);
+    assert!(output.status.success());
+
+    let state = github.state();
+    assert_eq!(state.commits.len(), 1);
+    assert_eq!(
+        state.commits[0]["message"],
+        "Registry promotion (Foo, Bar, 0.9/testing -> 0.9/stable)"
+    );
+    assert_eq!(state.blobs.len(), 4);
+    drop(state);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Bar/index.js",
+            "0.9/stable/Bar/static/icon.png",
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/banner.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+            "0.9/testing/metadata.json",
+            "0.9/testing/versioning.json",
+        ]
+    );
+    assert_eq!(
+        tree["0.9/stable/Bar/index.js"],
+        blob_sha(b"// Bar/index.js")
+    );
+
+    assert_eq!(
+        github.blob_json(&tree["0.9/stable/versioning.json"])["sources"],
+        json!([
+            source("Bar", "1.0.0"),
+            source("Foo", "1.1.0"),
+            source("Other", "1.0.0")
+        ])
+    );
+    assert_eq!(
+        github.blob_json(&tree["0.9/stable/metadata.json"]),
+        json!({
+            "community-extensions": { "Bar": metadata_extension(), "Foo": metadata_extension() },
+            "other-extensions": { "Other": metadata_extension() }
+        })
+    );
+}
+
+#[test]
+fn failed_promotion_promotes_nothing() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.1.0"), source("Bar", "1.0.0")]);
+
+    let output = github.run_command(&["promote"], &[("EXTENSION_ID", "Foo, Bar, Other")]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(
+            "The Other extension can't be promoted: it is not part of the testing branch"
+        )
+    );
+    assert!(github.state().commits.is_empty());
+}
+
+#[test]
+fn blocked_repository() {
+    let github = MockGitHub::start();
+    registry(&github, &[source("Foo", "1.1.0"), source("Bar", "1.0.0")]);
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/blocklist.json"),
+        &json!([
+            { "repository": "community-extensions", "id": "Bar", "reason": "Malware" },
+            { "repository": "other-extensions", "reason": "Abandoned" }
+        ]),
+    );
+
+    // The rules apply to the repository owning the extension on the testing branch
+    let output = github.run_command(&["promote", "Bar"], &[]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("The Bar extension is blocked from the registry (Malware), ignoring it")
+    );
+    assert!(github.state().commits.is_empty());
+
+    assert!(
+        github
+            .run_command(&["promote", "Foo"], &[])
+            .status
+            .success()
+    );
+    assert_eq!(github.state().commits.len(), 1);
+}
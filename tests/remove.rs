@@ -0,0 +1,115 @@
+mod common;
+
+use common::{
+    BASE_COMMIT, BRANCH, Backend, MockGitHub, REGISTRY, REPOSITORY, metadata_extension,
+    publish_extension, registry_branch, source, versioning,
+};
+use serde_json::{Value, json};
+
+fn registry(github: &MockGitHub) {
+    registry_branch(
+        github,
+        BRANCH,
+        &[source("Foo", "1.0.0"), source("Other", "1.0.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension() },
+            "other-extensions": { "Other": metadata_extension() }
+        }),
+    );
+    publish_extension(github, REGISTRY, "master", "Foo");
+    publish_extension(github, REGISTRY, "master", "Other");
+}
+
+#[test]
+fn takedown() {
+    let github = MockGitHub::start();
+    registry(&github);
+
+    let output = github.run_command(&["remove", "Foo", "--reason", "Malware"], &[]);
+    assert!(output.status.success());
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/takedowns.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(tree["0.9/stable/Foo/index.js"], Value::Null);
+    assert_eq!(tree["0.9/stable/Foo/static/icon.png"], Value::Null);
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Other", "1.0.0")])
+    );
+    assert_eq!(
+        github.blob_json(&tree["0.9/stable/metadata.json"]),
+        json!({ "other-extensions": { "Other": metadata_extension() } })
+    );
+
+    let registry_takedowns = github.blob_json(&tree["0.9/stable/takedowns.json"]);
+    assert_eq!(registry_takedowns.as_array().unwrap().len(), 1);
+    assert_eq!(registry_takedowns[0]["id"], "Foo");
+    assert_eq!(registry_takedowns[0]["repository"], "community-extensions");
+    assert_eq!(registry_takedowns[0]["reason"], "Malware");
+
+    let state = github.state();
+    assert_eq!(state.commits[0]["parents"], json!([BASE_COMMIT]));
+    assert_eq!(
+        state.commits[0]["message"],
+        "Registry takedown (Foo, 0.9/stable)"
+    );
+    assert_eq!(state.references[0].1["sha"], "commit-0");
+}
+
+#[test]
+fn taken_down_extension_is_not_added_again() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Other", "1.0.0")],
+        &json!({ "other-extensions": { "Other": metadata_extension() } }),
+    );
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/takedowns.json"),
+        &json!([{ "id": "Foo", "time": "2025-05-01T00:00:00.000Z" }]),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.1.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert!(github.state().commits.is_empty());
+}
+
+#[test]
+fn already_taken_down() {
+    let github = MockGitHub::start();
+    registry(&github);
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/takedowns.json"),
+        &json!([{ "id": "Gone", "time": "2025-05-01T00:00:00.000Z" }]),
+    );
+
+    assert!(
+        github
+            .run_command(&["remove", "Gone"], &[])
+            .status
+            .success()
+    );
+    assert!(github.state().commits.is_empty());
+}
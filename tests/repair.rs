@@ -0,0 +1,187 @@
+mod common;
+
+use common::{
+    BASE_COMMIT, BASE_TREE, BRANCH, Backend, MockGitHub, REGISTRY, metadata_extension,
+    registry_branch, source, versioning,
+};
+use serde_json::{Value, json};
+
+fn repository_versioning(sources: &[Value], build_time: &str) -> Value {
+    let mut versioning = versioning(sources);
+    versioning["buildTime"] = json!(build_time);
+    versioning
+}
+
+fn repository(github: &MockGitHub, name: &str, versioning: &Value) {
+    github.add_json(
+        &format!("paperback-community/{name}"),
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        versioning,
+    );
+}
+
+fn repair(github: &MockGitHub, env: &[(&str, &str)]) -> bool {
+    github.run_command(&["repair"], env).status.success()
+}
+
+#[test]
+fn lost_metadata() {
+    let github = MockGitHub::start();
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[
+            source("Foo", "1.0.0"),
+            source("Bar", "1.0.0"),
+            source("Qux", "1.0.0"),
+        ]),
+    );
+    github.add_file(REGISTRY, "master", &format!("{BRANCH}/metadata.json"), b"{");
+    repository(
+        &github,
+        "community-extensions",
+        &repository_versioning(&[source("Foo", "1.0.0")], "2025-05-01T00:00:00.000Z"),
+    );
+    repository(
+        &github,
+        "other-extensions",
+        &repository_versioning(
+            &[source("Bar", "1.0.0"), source("Unpublished", "1.0.0")],
+            "2025-06-01T00:00:00.000Z",
+        ),
+    );
+
+    assert!(repair(
+        &github,
+        &[(
+            "REPOSITORIES",
+            "community-extensions, paperback-community/other-extensions,missing-extensions"
+        )]
+    ));
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        ["0.9/stable/metadata.json"]
+    );
+    assert_eq!(
+        github.blob_json(&tree["0.9/stable/metadata.json"]),
+        json!({
+            "community-extensions": {
+                "Foo": {
+                    "build_time": "2025-05-01T00:00:00.000Z",
+                    "built_with": { "toolchain": "1.0.0", "types": "1.0.0-alpha.20" }
+                }
+            },
+            "other-extensions": {
+                "Bar": {
+                    "build_time": "2025-06-01T00:00:00.000Z",
+                    "built_with": { "toolchain": "1.0.0", "types": "1.0.0-alpha.20" }
+                }
+            }
+        })
+    );
+
+    let state = github.state();
+    assert_eq!(state.trees[0]["base_tree"], BASE_TREE);
+    assert_eq!(state.commits[0]["parents"], json!([BASE_COMMIT]));
+    assert_eq!(state.commits[0]["message"], "Registry repair (0.9/stable)");
+    assert_eq!(state.references[0].1["sha"], "commit-0");
+}
+
+#[test]
+fn stale_metadata() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0"), source("Bar", "1.0.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension(), "Gone": metadata_extension() }
+        }),
+    );
+    // Foo stays with the repository it belonged to, even though another one provides it as well
+    repository(
+        &github,
+        "community-extensions",
+        &repository_versioning(&[source("Foo", "1.0.0")], "2025-05-01T00:00:00.000Z"),
+    );
+    repository(
+        &github,
+        "other-extensions",
+        &repository_versioning(
+            &[source("Foo", "1.0.0"), source("Bar", "1.0.0")],
+            "2025-06-01T00:00:00.000Z",
+        ),
+    );
+
+    assert!(repair(&github, &[("REPOSITORIES", "other-extensions")]));
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        github.blob_json(&tree["0.9/stable/metadata.json"]),
+        json!({
+            "community-extensions": { "Foo": metadata_extension() },
+            "other-extensions": {
+                "Bar": {
+                    "build_time": "2025-06-01T00:00:00.000Z",
+                    "built_with": { "toolchain": "1.0.0", "types": "1.0.0-alpha.20" }
+                }
+            }
+        })
+    );
+}
+
+#[test]
+fn correct_metadata() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    repository(
+        &github,
+        "community-extensions",
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+
+    assert!(repair(&github, &[]));
+    assert!(github.state().trees.is_empty());
+}
+
+#[test]
+fn unknown_repositories() {
+    let github = MockGitHub::start();
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+
+    let output = github.run_command(&["repair"], &[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("REPOSITORIES"));
+}
+
+#[test]
+fn unavailable_metadata() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_failure("/metadata.json", 403, &[], "Resource not accessible");
+
+    // A metadata file which can't be fetched is not rebuilt from scratch
+    let output = github.run_command(&["repair"], &[("HTTP_RETRIES", "0")]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("was forbidden"));
+    assert!(github.state().trees.is_empty());
+}
@@ -0,0 +1,1377 @@
+mod common;
+
+use common::{
+    BASE_COMMIT, BASE_TREE, BRANCH, Backend, MOVED_COMMIT, MOVED_TREE, MockGitHub, REGISTRY,
+    REPOSITORY, TESTING, metadata_extension, publish_extension, registry_branch, source,
+    versioning,
+};
+use serde_json::{Value, json};
+
+fn assert_committed(github: &MockGitHub) {
+    let state = github.state();
+
+    assert_eq!(state.commits.len(), 1);
+    assert_eq!(state.trees[0]["base_tree"], BASE_TREE);
+    assert_eq!(state.commits[0]["tree"], "tree-0");
+    assert_eq!(state.commits[0]["parents"], json!([BASE_COMMIT]));
+    assert_eq!(
+        state.commits[0]["message"],
+        "Registry management (community-extensions, 0.9/stable)"
+    );
+
+    assert_eq!(state.references.len(), 1);
+    assert_eq!(
+        state.references[0].0,
+        "paperback-community/extensions/refs/heads/master"
+    );
+    assert_eq!(state.references[0].1["sha"], "commit-0");
+}
+
+fn assert_not_committed(github: &MockGitHub) {
+    let state = github.state();
+
+    assert!(state.blobs.is_empty());
+    assert!(state.trees.is_empty());
+    assert!(state.commits.is_empty());
+    assert!(state.references.is_empty());
+}
+
+#[test]
+fn addition() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Other", "1.0.0")],
+        &json!({ "other-extensions": { "Other": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/index.js"]),
+        b"// Foo gh-pages"
+    );
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/static/icon.png"]),
+        [0x89, b'P', b'N', b'G', 0x00, 0xff]
+    );
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.0.0"), source("Other", "1.0.0")])
+    );
+
+    let registry_metadata = github.blob_json(&tree["0.9/stable/metadata.json"]);
+    assert_eq!(
+        registry_metadata["community-extensions"]["Foo"],
+        metadata_extension()
+    );
+    assert_eq!(
+        registry_metadata["other-extensions"]["Other"],
+        metadata_extension()
+    );
+}
+
+#[test]
+fn update() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0"), source("Bar", "1.0.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension(), "Bar": metadata_extension() }
+        }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.1.0"), source("Bar", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Bar", "1.0.0"), source("Foo", "1.1.0")])
+    );
+}
+
+#[test]
+fn rollback() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.1.0"), source("Bar", "1.1.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension(), "Bar": metadata_extension() }
+        }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0"), source("Bar", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+
+    // Only Foo may be rolled back, Bar keeps its higher version
+    let output = github.run(&[("ROLLBACK", "Foo, Qux")]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Rolling back extension: Foo"));
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Bar", "1.1.0"), source("Foo", "1.0.0")])
+    );
+}
+
+#[test]
+fn lower_version_without_rollback() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.1.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_not_committed(&github);
+}
+
+#[test]
+fn invalid_registry_version() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "latest")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "The version of the Foo extension in the registry is not valid semver, replacing it"
+    ));
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.0.0")])
+    );
+}
+
+#[test]
+fn invalid_types_version() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    let mut repository_versioning = versioning(&[source("Foo", "1.0.0")]);
+    repository_versioning["builtWith"]["types"] = json!("next");
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &repository_versioning,
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    let output = github.run(&[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "The repository was build with a @paperback/types version next which is not valid semver"
+    ));
+    assert_not_committed(&github);
+}
+
+#[test]
+fn prerelease_on_stable() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.1.0-beta.1"), source("Bar", "1.0.0-rc.1")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(
+        "The Foo extension is invalid, ignoring it: version: \"1.1.0-beta.1\" is a prerelease, which can only be published to testing branches"
+    ));
+    assert!(stderr.contains("The Bar extension is invalid, ignoring it: version:"));
+    assert_not_committed(&github);
+}
+
+#[test]
+fn prerelease_on_testing() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        "0.9/testing",
+        &[source("Foo", "1.1.0-beta.1")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        "0.9/testing/versioning.json",
+        &versioning(&[source("Foo", "1.1.0-beta.2")]),
+    );
+    for path in [
+        "0.9/testing/Foo/index.js",
+        "0.9/testing/Foo/static/icon.png",
+    ] {
+        github.add_file(REPOSITORY, "gh-pages", path, b"// Foo testing");
+    }
+
+    assert!(github.run(&[("BRANCH", "0.9/testing")]).status.success());
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/testing/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.1.0-beta.2")])
+    );
+}
+
+#[test]
+fn stable_addition_requires_testing() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    github.add_json(
+        REGISTRY,
+        "master",
+        "0.9/testing/versioning.json",
+        &versioning(&[source("Foo", "1.1.0"), source("Bar", "0.9.0")]),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[
+            source("Foo", "1.0.0"),
+            source("Bar", "1.0.0"),
+            source("Baz", "1.0.0"),
+        ]),
+    );
+    for id in ["Foo", "Bar", "Baz"] {
+        publish_extension(&github, REPOSITORY, "gh-pages", id);
+    }
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.0.0")])
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    for id in ["Bar", "Baz"] {
+        assert!(stderr.contains(&format!(
+            "The {id} extension has to be published to the testing branch first, with the same or a higher version, ignoring it"
+        )));
+    }
+}
+
+#[test]
+fn stable_addition_without_testing_branch() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    assert!(github.state().commits.is_empty());
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(
+        "No registry versioning file found for 0.9/testing, extensions can't be added to the stable branch"
+    ));
+    assert!(stderr.contains(
+        "The Foo extension has to be published to the testing branch first, with the same or a higher version, ignoring it"
+    ));
+}
+
+#[test]
+fn testing_badges() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+
+    let mut beta = source("Foo", "1.0.0");
+    beta["badges"] = json!([
+        { "label": "beta", "textColor": "#000000", "backgroundColor": "#ffffff" }
+    ]);
+    let mut popular = source("Bar", "1.0.0");
+    popular["badges"] = json!([
+        { "label": "Popular", "textColor": "#000000", "backgroundColor": "#ffffff" }
+    ]);
+    registry_branch(&github, TESTING, &[popular.clone()], &json!({}));
+
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[beta, popular.clone()]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "The Foo extension is invalid, ignoring it: badges: \"beta\" can only be used on testing branches"
+    ));
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(registry_versioning["sources"], json!([popular]));
+}
+
+/*
+ * Publishes the Foo extension with the same version in the registry and the repository, where
+ * only the index.js of the repository differs.
+ */
+fn changed_content(github: &MockGitHub) {
+    registry_branch(
+        github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    publish_extension(github, REGISTRY, "master", "Foo");
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(github, REPOSITORY, "gh-pages", "Foo");
+}
+
+#[test]
+fn changed_content_is_reported() {
+    let github = MockGitHub::start();
+    changed_content(&github);
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("The content of the Foo extension changed without a version bump")
+    );
+    assert_not_committed(&github);
+}
+
+#[test]
+fn changed_content_fails() {
+    let github = MockGitHub::start();
+    changed_content(&github);
+
+    let output = github.run(&[("CHANGED_CONTENT", "fail")]);
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains("The content of extensions changed without a version bump: Foo")
+    );
+    assert_not_committed(&github);
+}
+
+#[test]
+fn changed_content_is_updated() {
+    let github = MockGitHub::start();
+    changed_content(&github);
+
+    assert!(
+        github
+            .run(&[("CHANGED_CONTENT", "update")])
+            .status
+            .success()
+    );
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/index.js"]),
+        b"// Foo gh-pages"
+    );
+}
+
+#[test]
+fn legacy_layout() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    for (repository, branch) in [(REGISTRY, "master"), (REPOSITORY, "gh-pages")] {
+        github.add_file(
+            repository,
+            branch,
+            &format!("{BRANCH}/Foo/index.js"),
+            b"// Foo",
+        );
+        github.add_file(
+            repository,
+            branch,
+            &format!("{BRANCH}/Foo/static/icon.png"),
+            b"icon",
+        );
+    }
+    // Files the registry never received before nested files were synced
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Foo/static/covers/cover.png"),
+        b"cover",
+    );
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Foo/lib/helpers.js"),
+        b"// helpers",
+    );
+
+    let output = github.run(&[("CHANGED_CONTENT", "fail")]);
+    assert!(output.status.success());
+    assert!(
+        !String::from_utf8_lossy(&output.stderr)
+            .contains("The content of the Foo extension changed without a version bump")
+    );
+    assert_not_committed(&github);
+}
+
+#[test]
+fn unchanged_files() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    // Only the index.js differs between the registry and the repository
+    publish_extension(&github, REGISTRY, "master", "Foo");
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.1.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(github.state().blobs.len(), 3);
+}
+
+#[test]
+fn dropped_files() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Foo");
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Foo/static/old-icon.png"),
+        b"old icon",
+    );
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Foo/static/images/old-banner.png"),
+        b"old banner",
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.1.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/images/old-banner.png",
+            "0.9/stable/Foo/static/old-icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(tree["0.9/stable/Foo/static/old-icon.png"], Value::Null);
+    assert_eq!(
+        tree["0.9/stable/Foo/static/images/old-banner.png"],
+        Value::Null
+    );
+}
+
+#[test]
+fn deletion() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0"), source("Bar", "1.0.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension(), "Bar": metadata_extension() }
+        }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Foo");
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Bar", "1.0.0")]),
+    );
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(tree["0.9/stable/Foo/index.js"], Value::Null);
+    assert_eq!(tree["0.9/stable/Foo/static/icon.png"], Value::Null);
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Bar", "1.0.0")])
+    );
+
+    let registry_metadata = github.blob_json(&tree["0.9/stable/metadata.json"]);
+    assert_eq!(
+        registry_metadata,
+        json!({ "community-extensions": { "Bar": metadata_extension() } })
+    );
+}
+
+#[test]
+fn nested_files() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Bar", "1.0.0")],
+        &json!({ "community-extensions": { "Bar": metadata_extension() } }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Bar");
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Bar/static/images/banner.png"),
+        b"banner",
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Foo/static/images/banner.png"),
+        b"banner",
+    );
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Foo/chunks/parser.js"),
+        b"// parser",
+    );
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Bar/index.js",
+            "0.9/stable/Bar/static/icon.png",
+            "0.9/stable/Bar/static/images/banner.png",
+            "0.9/stable/Foo/chunks/parser.js",
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/icon.png",
+            "0.9/stable/Foo/static/images/banner.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(tree["0.9/stable/Bar/static/images/banner.png"], Value::Null);
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/static/images/banner.png"]),
+        b"banner"
+    );
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/chunks/parser.js"]),
+        b"// parser"
+    );
+}
+
+#[test]
+fn first_time_creation() {
+    let github = MockGitHub::start();
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert!(tree.contains_key("0.9/stable/Foo/index.js"));
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["repository"]["name"],
+        "Paperback Community Extensions (0.9)"
+    );
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.0.0")])
+    );
+
+    let registry_metadata = github.blob_json(&tree["0.9/stable/metadata.json"]);
+    assert_eq!(
+        registry_metadata,
+        json!({ "community-extensions": { "Foo": metadata_extension() } })
+    );
+}
+
+#[test]
+fn template_skipping() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("ExtensionTemplate", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "ExtensionTemplate");
+
+    assert!(github.run(&[]).status.success());
+    assert_not_committed(&github);
+}
+
+#[test]
+fn blocklist() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/blocklist.json"),
+        &json!([
+            { "id": "Foo", "versions": ">=1.1.0 <1.2.0", "reason": "Broken release" },
+            { "id": "Bar", "reason": "Malware" },
+            { "repository": "other-extensions", "reason": "Abandoned" },
+            { "content_rating": "ADULT", "reason": "No adult extensions" },
+            { "intents": 16, "reason": "Requires a Cloudflare bypass" }
+        ]),
+    );
+
+    let mut adult = source("Adult", "1.0.0");
+    adult["contentRating"] = json!("ADULT");
+    let mut cloudflare = source("Cloudflare", "1.0.0");
+    cloudflare["capabilities"] = json!([1, 16, 64]);
+    registry_branch(&github, TESTING, &[source("Baz", "1.0.0")], &json!({}));
+
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[
+            source("Foo", "1.1.0"),
+            source("Bar", "1.0.0"),
+            source("Baz", "1.0.0"),
+            adult,
+            cloudflare,
+        ]),
+    );
+    for id in ["Foo", "Bar", "Baz", "Adult", "Cloudflare"] {
+        publish_extension(&github, REPOSITORY, "gh-pages", id);
+    }
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Baz/index.js",
+            "0.9/stable/Baz/static/icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Baz", "1.0.0"), source("Foo", "1.0.0")])
+    );
+}
+
+#[test]
+fn source_round_trip() {
+    let github = MockGitHub::start();
+
+    // Unknown content ratings, languages and intents are written back as they were read
+    let mut combined = source("Bar", "1.0.0");
+    combined["contentRating"] = json!("EVERYONE");
+    combined["language"] = json!("multi");
+    combined["capabilities"] = json!(4161);
+    let mut listed = source("Foo", "1.0.0");
+    listed["contentRating"] = json!("MATURE");
+    listed["language"] = json!(null);
+    listed["capabilities"] = json!([1, 64, 256]);
+
+    registry_branch(
+        &github,
+        BRANCH,
+        &[combined.clone(), listed.clone()],
+        &json!({
+            "other-extensions": { "Bar": metadata_extension(), "Foo": metadata_extension() }
+        }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Qux", "1.0.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Qux", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Qux");
+
+    assert!(github.run(&[]).status.success());
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([combined, listed, source("Qux", "1.0.0")])
+    );
+}
+
+#[test]
+fn invalid_sources() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+
+    let mut unknown_rating = source("Bar", "1.0.0");
+    unknown_rating["contentRating"] = json!("EVERYONE");
+    let mut null_developer = source("Baz", "1.0.0");
+    null_developer["developers"] = json!([null]);
+    let mut invalid_language = source("Qux", "1.0.0");
+    invalid_language["language"] = json!("English");
+    invalid_language["badges"] = json!([null]);
+    registry_branch(&github, TESTING, &[source("Valid", "1.0.0")], &json!({}));
+
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[
+            source("../Foo", "1.0.0"),
+            source("Foo", "1.0"),
+            unknown_rating,
+            null_developer,
+            invalid_language,
+            source("Icon", "1.0.0"),
+            source("Valid", "1.0.0"),
+        ]),
+    );
+    for id in ["Foo", "Bar", "Baz", "Qux", "Valid"] {
+        publish_extension(&github, REPOSITORY, "gh-pages", id);
+    }
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Icon/index.js"),
+        b"// Icon",
+    );
+
+    let output = github.run(&[]);
+    assert!(output.status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Valid", "1.0.0")])
+    );
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(
+        "The ../Foo extension is invalid, ignoring it: id: \"../Foo\" may only contain letters, digits, dashes and underscores; icon: static/icon.png does not exist"
+    ));
+    assert!(stderr.contains("The Foo extension is invalid, ignoring it: version:"));
+    assert!(stderr.contains(
+        "The Bar extension is invalid, ignoring it: contentRating: \"EVERYONE\" is not one of SAFE, MATURE or ADULT"
+    ));
+    assert!(stderr.contains(
+        "The Baz extension is invalid, ignoring it: developers: it contains null entries"
+    ));
+    assert!(stderr.contains(
+        "The Qux extension is invalid, ignoring it: language: \"English\" is not a valid language tag; badges: it contains null entries"
+    ));
+    assert!(stderr.contains(
+        "The Icon extension is invalid, ignoring it: icon: static/icon.png does not exist"
+    ));
+}
+
+#[test]
+fn cross_repository_collision() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0")],
+        &json!({ "other-extensions": { "Foo": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "2.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_not_committed(&github);
+}
+
+#[test]
+fn registry_snapshot() {
+    let github = MockGitHub::start();
+    // The branch moved after its head was fetched, the files of that head should be used
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Other", "1.0.0"), source("Bar", "1.0.0")],
+        &json!({
+            "other-extensions": { "Other": metadata_extension(), "Bar": metadata_extension() }
+        }),
+    );
+    github.add_json(
+        REGISTRY,
+        BASE_COMMIT,
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Other", "1.0.0")]),
+    );
+    github.add_json(
+        REGISTRY,
+        BASE_COMMIT,
+        &format!("{BRANCH}/metadata.json"),
+        &json!({ "other-extensions": { "Other": metadata_extension() } }),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    github.add_json(
+        REGISTRY,
+        BASE_COMMIT,
+        &format!("{TESTING}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    let registry_versioning = github.blob_json(&tree["0.9/stable/versioning.json"]);
+    assert_eq!(
+        registry_versioning["sources"],
+        json!([source("Foo", "1.0.0"), source("Other", "1.0.0")])
+    );
+
+    let registry_metadata = github.blob_json(&tree["0.9/stable/metadata.json"]);
+    assert_eq!(
+        registry_metadata,
+        json!({
+            "community-extensions": { "Foo": metadata_extension() },
+            "other-extensions": { "Other": metadata_extension() }
+        })
+    );
+}
+
+#[test]
+fn many_extensions() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    let ids = (0..30)
+        .map(|index| format!("Extension{index:02}"))
+        .collect::<Vec<String>>();
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(
+            &ids.iter()
+                .map(|id| source(id, "1.0.0"))
+                .collect::<Vec<Value>>(),
+        ),
+    );
+    registry_branch(
+        &github,
+        TESTING,
+        &ids.iter()
+            .map(|id| source(id, "1.0.0"))
+            .collect::<Vec<Value>>(),
+        &json!({}),
+    );
+    for id in &ids {
+        publish_extension(&github, REPOSITORY, "gh-pages", id);
+    }
+
+    assert!(github.run(&[("CONCURRENCY", "4")]).status.success());
+    assert_committed(&github);
+
+    let tree = github.tree_entries();
+    assert_eq!(tree.len(), ids.len() * 2 + 2);
+    for id in &ids {
+        assert_eq!(
+            github.blob(&tree[&format!("0.9/stable/{id}/index.js")]),
+            format!("// {id} gh-pages").as_bytes()
+        );
+    }
+}
+
+#[test]
+fn missing_extension_file() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[
+            source("Bar", "1.0.0"),
+            source("Foo", "1.0.0"),
+            source("Qux", "1.0.0"),
+        ]),
+    );
+    registry_branch(
+        &github,
+        TESTING,
+        &[
+            source("Bar", "1.0.0"),
+            source("Foo", "1.0.0"),
+            source("Qux", "1.0.0"),
+        ],
+        &json!({}),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Qux");
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Foo/static/icon.png"),
+        b"icon",
+    );
+
+    let output = github.run(&[]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("The Foo extension failed"));
+
+    let state = github.state();
+    assert!(state.trees.is_empty());
+    assert!(state.references.is_empty());
+}
+
+#[test]
+fn transfer() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Foo", "1.0.0"), source("Bar", "1.0.0")],
+        &json!({
+            "other-extensions": { "Foo": metadata_extension(), "Bar": metadata_extension() }
+        }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Foo");
+    github.add_file(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/Foo/static/old-icon.png"),
+        b"old icon",
+    );
+    github.add_json(
+        REGISTRY,
+        "master",
+        &format!("{BRANCH}/transfers.json"),
+        &json!([
+            { "id": "Foo", "from": "other-extensions", "to": "community-extensions" },
+            { "id": "Bar", "from": "other-extensions", "to": "third-extensions" }
+        ]),
+    );
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0"), source("Bar", "1.0.0")]),
+    );
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+
+    assert!(github.run(&[]).status.success());
+    assert_committed(&github);
+
+    // Bar may only be transferred to another repository, so it is left alone
+    let tree = github.tree_entries();
+    assert_eq!(
+        tree.keys().collect::<Vec<&String>>(),
+        [
+            "0.9/stable/Foo/index.js",
+            "0.9/stable/Foo/static/old-icon.png",
+            "0.9/stable/metadata.json",
+            "0.9/stable/transfers.json",
+            "0.9/stable/versioning.json",
+        ]
+    );
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/index.js"]),
+        b"// Foo gh-pages"
+    );
+    assert_eq!(tree["0.9/stable/Foo/static/old-icon.png"], Value::Null);
+
+    let registry_metadata = github.blob_json(&tree["0.9/stable/metadata.json"]);
+    assert_eq!(
+        registry_metadata,
+        json!({
+            "community-extensions": { "Foo": metadata_extension() },
+            "other-extensions": { "Bar": metadata_extension() }
+        })
+    );
+
+    let registry_transfers = github.blob_json(&tree["0.9/stable/transfers.json"]);
+    assert_eq!(
+        registry_transfers,
+        json!([{ "id": "Bar", "from": "other-extensions", "to": "third-extensions" }])
+    );
+}
+
+#[test]
+fn dry_run() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        BRANCH,
+        &[source("Bar", "1.0.0")],
+        &json!({ "community-extensions": { "Bar": metadata_extension() } }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Bar");
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0"), source("Bar", "1.1.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    publish_extension(&github, REPOSITORY, "gh-pages", "Bar");
+    github.add_file(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/Bar/static/new.png"),
+        b"new",
+    );
+
+    let output = github.run(&[("DRY_RUN", "true")]);
+    assert!(output.status.success());
+    assert_not_committed(&github);
+
+    // The logs go to stderr, leaving only the changes on stdout
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with(
+        "Changes which would be committed to paperback-community/extensions (master):\n"
+    ));
+    assert!(!stdout.contains("INFO"));
+    assert!(stdout.contains("add     0.9/stable/Foo/index.js"));
+    assert!(stdout.contains("add     0.9/stable/Foo/static/icon.png"));
+    // Files new to an updated extension are added as well
+    assert!(stdout.contains("change  0.9/stable/Bar/index.js"));
+    assert!(stdout.contains("add     0.9/stable/Bar/static/new.png"));
+    assert!(stdout.contains("change  0.9/stable/versioning.json"));
+}
+
+#[test]
+fn configured_registry() {
+    let github = MockGitHub::start();
+    github.add_json(
+        "mirror/registry",
+        "main",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[]),
+    );
+    github.add_json(
+        "mirror/registry",
+        "main",
+        &format!("{BRANCH}/metadata.json"),
+        &json!({}),
+    );
+    github.add_json(
+        "mirror/registry",
+        "main",
+        &format!("{TESTING}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    github.add_json(
+        "mirror/extensions",
+        "pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    publish_extension(&github, "mirror/extensions", "pages", "Foo");
+
+    assert!(
+        github
+            .run(&[
+                ("REGISTRY_REPOSITORY", "mirror/registry"),
+                ("REGISTRY_BRANCH", "main"),
+                ("REPOSITORY_BRANCH", "pages"),
+                ("REPOSITORY_OWNER", "mirror"),
+                ("REPOSITORY", "mirror/extensions"),
+            ])
+            .status
+            .success()
+    );
+
+    let tree = github.tree_entries();
+    assert_eq!(
+        github.blob(&tree["0.9/stable/Foo/index.js"]),
+        b"// Foo pages"
+    );
+
+    let registry_metadata = github.blob_json(&tree["0.9/stable/metadata.json"]);
+    assert_eq!(
+        registry_metadata,
+        json!({ "extensions": { "Foo": metadata_extension() } })
+    );
+
+    let state = github.state();
+    assert_eq!(
+        state.commits[0]["message"],
+        "Registry management (extensions, 0.9/stable)"
+    );
+    assert_eq!(state.references[0].0, "mirror/registry/refs/heads/main");
+}
+
+#[test]
+fn repository_outside_of_the_owner() {
+    let github = MockGitHub::start();
+
+    let output = github.run(&[("REPOSITORY", "someone-else/extensions")]);
+    assert!(!output.status.success());
+    assert_not_committed(&github);
+}
+
+#[test]
+fn concurrent_registry_update() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    github.add_concurrent_json(
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Other", "1.0.0")]),
+    );
+    github.add_concurrent_json(
+        &format!("{BRANCH}/metadata.json"),
+        &json!({ "other-extensions": { "Other": metadata_extension() } }),
+    );
+
+    assert!(github.run(&[]).status.success());
+
+    let state = github.state();
+    assert_eq!(state.trees.len(), 2);
+    assert_eq!(state.trees[1]["base_tree"], MOVED_TREE);
+    assert_eq!(state.commits.len(), 2);
+    assert_eq!(state.commits[1]["parents"], json!([MOVED_COMMIT]));
+    assert_eq!(state.references.len(), 1);
+    assert_eq!(state.references[0].1["sha"], "commit-1");
+    assert_eq!(state.references[0].1["force"], false);
+    drop(state);
+
+    let tree = github.state().trees[1]["tree"].clone();
+    let versioning_sha = tree
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|entry| entry["path"] == "0.9/stable/versioning.json")
+        .unwrap()["sha"]
+        .clone();
+    assert_eq!(
+        github.blob_json(&versioning_sha)["sources"],
+        json!([source("Foo", "1.0.0"), source("Other", "1.0.0")])
+    );
+}
+
+#[test]
+fn concurrent_registry_update_without_retries() {
+    let github = MockGitHub::start();
+    registry_branch(&github, BRANCH, &[], &json!({}));
+    github.add_json(
+        REPOSITORY,
+        "gh-pages",
+        &format!("{BRANCH}/versioning.json"),
+        &versioning(&[source("Foo", "1.0.0")]),
+    );
+    registry_branch(&github, TESTING, &[source("Foo", "1.0.0")], &json!({}));
+    publish_extension(&github, REPOSITORY, "gh-pages", "Foo");
+    github.add_concurrent_json(&format!("{BRANCH}/versioning.json"), &versioning(&[]));
+
+    assert!(!github.run(&[("COMMIT_RETRIES", "0")]).status.success());
+    assert!(github.state().references.is_empty());
+}
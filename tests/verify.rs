@@ -0,0 +1,113 @@
+mod common;
+
+use common::{
+    Backend, MockGitHub, REGISTRY, metadata_extension, publish_extension, registry_branch, source,
+    versioning,
+};
+use serde_json::{Value, json};
+
+fn verify(github: &MockGitHub) -> (bool, Value) {
+    // The logs go to stderr with the default log level, stdout only holds the report
+    let output = github.run_command(&["verify"], &[]);
+
+    (
+        output.status.success(),
+        serde_json::from_slice(&output.stdout).unwrap(),
+    )
+}
+
+#[test]
+fn consistent_registry() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        "0.9/stable",
+        &[source("Foo", "1.0.0"), source("Bar", "1.0.0")],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension() },
+            "other-extensions": { "Bar": metadata_extension() }
+        }),
+    );
+    registry_branch(
+        &github,
+        "0.9/testing",
+        &[source("Foo", "1.1.0")],
+        &json!({ "community-extensions": { "Foo": metadata_extension() } }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Foo");
+    publish_extension(&github, REGISTRY, "master", "Bar");
+    github.add_file(REGISTRY, "master", "0.9/testing/Foo/index.js", b"// Foo");
+    // Extension files named like a versioning file don't make up a branch
+    github.add_file(
+        REGISTRY,
+        "master",
+        "0.9/testing/Foo/static/versioning.json",
+        b"{}",
+    );
+
+    let (success, drift) = verify(&github);
+    assert!(success);
+    assert_eq!(drift, json!([]));
+}
+
+#[test]
+fn inconsistent_registry() {
+    let github = MockGitHub::start();
+    registry_branch(
+        &github,
+        "0.9/stable",
+        &[
+            source("Foo", "1.0.0"),
+            source("Bar", "1.0.0"),
+            source("Qux", "1.0.0"),
+        ],
+        &json!({
+            "community-extensions": { "Foo": metadata_extension(), "Bar": metadata_extension() },
+            "other-extensions": { "Bar": metadata_extension(), "Gone": metadata_extension() }
+        }),
+    );
+    publish_extension(&github, REGISTRY, "master", "Foo");
+    github.add_file(REGISTRY, "master", "0.9/stable/Bar/index.js", b"// Bar");
+    github.add_file(REGISTRY, "master", "0.9/stable/Qux/index.js", b"// Qux");
+    github.add_file(REGISTRY, "master", "0.9/stable/Stray/index.js", b"// Stray");
+    github.add_file(
+        REGISTRY,
+        "master",
+        "0.9/testing/versioning.json",
+        &versioning(&[]).to_string().into_bytes(),
+    );
+    github.add_file(REGISTRY, "master", "1.0/stable/versioning.json", b"{");
+
+    let (success, drift) = verify(&github);
+    assert!(!success);
+    assert_eq!(
+        drift,
+        json!([
+            {
+                "kind": "missing_source",
+                "branch": "0.9/stable",
+                "id": "Gone",
+                "repository": "other-extensions"
+            },
+            {
+                "kind": "multiple_owners",
+                "branch": "0.9/stable",
+                "id": "Bar",
+                "repositories": ["community-extensions", "other-extensions"]
+            },
+            { "kind": "missing_owner", "branch": "0.9/stable", "id": "Qux" },
+            { "kind": "missing_index", "branch": "0.9/stable", "id": "Gone" },
+            {
+                "kind": "orphaned_directory",
+                "branch": "0.9/stable",
+                "directory": "0.9/stable/Stray"
+            },
+            { "kind": "missing_metadata", "branch": "0.9/testing" },
+            {
+                "kind": "invalid_file",
+                "path": "1.0/stable/versioning.json",
+                "message": "Something went wrong while deserializing the JSON of versioning.json: EOF while parsing an object at line 1 column 1"
+            }
+        ])
+    );
+}